@@ -0,0 +1,50 @@
+//! Compares writing 10,000 items through `Builder::into_json()` with writing them directly.
+
+#[macro_use]
+extern crate criterion;
+extern crate alfred;
+
+use alfred::json::Builder;
+use alfred::{Item, ItemBuilder, Modifier};
+use criterion::{Criterion, Throughput};
+use std::hint::black_box;
+use std::io::Write;
+
+fn items() -> Vec<Item<'static>> {
+    (0..10_000).map(|i| {
+        ItemBuilder::new(format!("Item {}", i))
+                    .subtitle(format!("/Users/example/Documents/item-{}.txt", i))
+                    .uid(format!("item-{}", i))
+                    .arg(format!("/Users/example/Documents/item-{}.txt", i))
+                    .icon_file(format!("/Users/example/Documents/item-{}.txt", i))
+                    .subtitle_mod(Modifier::Command, "Reveal in Finder")
+                    .variable("index", i.to_string())
+                    .into_item()
+    }).collect()
+}
+
+fn bench_json(c: &mut Criterion) {
+    let items = items();
+    let len = {
+        let mut output = Vec::new();
+        Builder::with_items(&items).write(&mut output).unwrap();
+        output.len()
+    };
+
+    let mut group = c.benchmark_group("json 10k items");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("into_json", |b| b.iter(|| {
+        let mut output = Vec::with_capacity(len);
+        write!(&mut output, "{}", Builder::with_items(black_box(&items)).into_json()).unwrap();
+        output
+    }));
+    group.bench_function("write", |b| b.iter(|| {
+        let mut output = Vec::with_capacity(len);
+        Builder::with_items(black_box(&items)).write(&mut output).unwrap();
+        output
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_json);
+criterion_main!(benches);
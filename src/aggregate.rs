@@ -0,0 +1,232 @@
+//! Combining the results of several sources queried concurrently
+//!
+//! Script filters often search several backends at once, such as local files, a web API, and
+//! history. An `Aggregator` runs each source on its own thread, then merges their items into one
+//! list ranked by the sources' weights, keeping only the best-ranked item for each `uid`.
+//!
+//! A source that fails doesn't fail the whole response: its error becomes a single error item
+//! after the results, built with `alfred::runner::error_item()`. The same goes for a source that
+//! panics. Sources run on their own threads, so the panic hook installed by
+//! `alfred::crash::install()` leaves their panics to the aggregator.
+//!
+//! # Ranking
+//!
+//! The item at position `i` (counting from 0) of a source with weight `w` scores `w / (i + 1)`,
+//! and items are sorted by descending score. With equal weights, the results of the sources are
+//! interleaved; a source with weight 2 gets its second item ranked alongside the other sources'
+//! first items. Items with the same score keep the order in which their sources were added.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # use std::io;
+//! # fn main() {
+//! use alfred::aggregate::Aggregator;
+//! use alfred::Item;
+//!
+//! let query = "rust";
+//! let items = Aggregator::new()
+//!                        .source("files", || Ok::<_, io::Error>(vec![Item::new("rust.txt")]))
+//!                        .weighted_source("history", 0.5, || {
+//!                            Ok::<_, io::Error>(vec![Item::new(format!("{} book", query))])
+//!                        })
+//!                        .source("api", || Err("service unavailable"))
+//!                        .run();
+//! assert_eq!(items[0].title, "rust.txt");
+//! assert_eq!(items[1].title, "rust book");
+//! assert_eq!(items[2].title, "Error: api failed");
+//! # }
+//! ```
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::thread;
+
+use collection::Items;
+use env::Environment;
+use runner::error_item;
+use Item;
+
+/// The error type returned by sources.
+pub type SourceError = Box<dyn error::Error + Send + Sync>;
+
+type Source<'a> = Box<dyn FnOnce() -> Result<Vec<Item<'a>>, SourceError> + Send + 'a>;
+
+/// Runs sources concurrently and merges their items.
+///
+/// See the module documentation for details.
+pub struct Aggregator<'a> {
+    sources: Vec<(String, f64, Source<'a>)>,
+    env: Environment
+}
+
+impl<'a> fmt::Debug for Aggregator<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Aggregator")
+         .field("sources", &self.sources.iter().map(|s| (&s.0, s.1)).collect::<Vec<_>>())
+         .finish()
+    }
+}
+
+impl<'a> Default for Aggregator<'a> {
+    fn default() -> Aggregator<'a> {
+        Aggregator::new()
+    }
+}
+
+impl<'a> Aggregator<'a> {
+    /// Returns a new `Aggregator` with no sources that reads the process environment.
+    pub fn new() -> Aggregator<'a> {
+        Aggregator {
+            sources: Vec::new(),
+            env: Environment::from_env()
+        }
+    }
+
+    /// Sets the environment used to check whether the workflow debugger is open, which controls
+    /// the detail shown in error items.
+    pub fn environment(mut self, env: Environment) -> Aggregator<'a> {
+        self.env = env;
+        self
+    }
+
+    /// Adds a source with weight 1.
+    ///
+    /// The name is used in the error item if the source fails.
+    pub fn source<S, F, E>(self, name: S, source: F) -> Aggregator<'a>
+        where S: Into<String>,
+              F: FnOnce() -> Result<Vec<Item<'a>>, E> + Send + 'a,
+              E: Into<SourceError>
+    {
+        self.weighted_source(name, 1.0, source)
+    }
+
+    /// Adds a source with the given weight.
+    ///
+    /// Sources with higher weights rank higher. See the module documentation for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` isn't positive and finite.
+    pub fn weighted_source<S, F, E>(mut self, name: S, weight: f64, source: F) -> Aggregator<'a>
+        where S: Into<String>,
+              F: FnOnce() -> Result<Vec<Item<'a>>, E> + Send + 'a,
+              E: Into<SourceError>
+    {
+        assert!(weight > 0.0 && weight.is_finite(), "weight must be positive and finite");
+        self.sources.push((name.into(), weight, Box::new(move || source().map_err(Into::into))));
+        self
+    }
+
+    /// Runs the sources concurrently and returns the merged items.
+    ///
+    /// Items with a `uid` already used by a higher-ranked item are removed. Error items for
+    /// failed sources follow the results, in the order the sources were added.
+    pub fn run(self) -> Items<'a> {
+        let debug = self.env.is_debug();
+        let mut ranked = Vec::new();
+        let mut errors = Vec::new();
+        thread::scope(|scope| {
+            let handles: Vec<_> = self.sources.into_iter().map(|(name, weight, source)| {
+                (name, weight, scope.spawn(source))
+            }).collect();
+            for (name, weight, handle) in handles {
+                let error = match handle.join() {
+                    Ok(Ok(items)) => {
+                        ranked.extend(items.into_iter().enumerate().map(|(i, item)| {
+                            (weight / (i + 1) as f64, item)
+                        }));
+                        continue;
+                    }
+                    Ok(Err(error)) => error,
+                    Err(panic) => panic_message(&*panic).into()
+                };
+                errors.push(error_item(&Failure { name, error }, debug));
+            }
+        });
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        let mut items = ranked.into_iter().map(|(_, item)| item).collect::<Items>().dedup_by_uid();
+        items.extend(errors);
+        items
+    }
+}
+
+/// The error describing a failed source.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) name: String,
+    pub(crate) error: SourceError
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed", self.name)
+    }
+}
+
+impl error::Error for Failure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(s) => format!("panicked: {}", s),
+        None => match panic.downcast_ref::<String>() {
+            Some(s) => format!("panicked: {}", s),
+            None => "panicked".to_owned()
+        }
+    }
+}
+
+#[test]
+fn test_aggregator() {
+    use ItemBuilder;
+
+    let item = |title: &'static str, uid| ItemBuilder::new(title).uid(uid).into_item();
+    let items = Aggregator::new()
+                           .environment(Environment::new())
+                           .source("a", || Ok::<_, SourceError>(vec![item("a1", "x"),
+                                                                     item("a2", "y")]))
+                           .source("broken", || Err("connection refused"))
+                           .weighted_source("b", 3.0, || {
+                               Ok::<_, SourceError>(vec![item("b1", "y"), item("b2", "z"),
+                                                         item("b3", "w"), item("b4", "x")])
+                           })
+                           .source("panics", || -> Result<Vec<Item>, SourceError> {
+                               panic!("oops")
+                           })
+                           .run();
+    let titles = items.iter().map(|i| i.title.to_string()).collect::<Vec<_>>();
+    assert_eq!(titles, ["b1", "b2", "a1", "b3", "Error: broken failed", "Error: panics failed"]);
+    assert_eq!(items[4].subtitle.as_deref(), Some("connection refused"));
+    assert_eq!(items[5].subtitle.as_deref(), Some("panicked: oops"));
+}
+
+#[test]
+fn test_aggregator_with_crash_hook() {
+    use crash;
+    use runner::Format;
+
+    let name = "aggregate::test_aggregator_with_crash_hook";
+    let output = crash::run_with_hook(name, Format::Json, || {
+        Aggregator::new()
+                   .environment(Environment::new())
+                   .source("a", || Ok::<_, SourceError>(vec![Item::new("a1")]))
+                   .source("panics", || -> Result<Vec<Item>, SourceError> { panic!("oops") })
+                   .run()
+                   .write_json(crash::stdout())
+                   .unwrap();
+    });
+    if let Some(output) = output {
+        let document = crash::json_document(&output);
+        let titles = document["items"].as_array().unwrap().iter()
+                                      .map(|item| item["title"].as_str().unwrap())
+                                      .collect::<Vec<_>>();
+        assert_eq!(titles, ["a1", "Error: panics failed"]);
+    }
+}
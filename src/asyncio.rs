@@ -0,0 +1,109 @@
+// Shared support for the `AsyncWrite` versions of the JSON and XML writers.
+//
+// The crate is written without `async fn`, so the writers serialize into a buffer up front and
+// return one of these hand-written futures to write the buffer out.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::AsyncWrite;
+
+/// Writes `buf[*pos..]` to `w`, advancing `pos` past the bytes that have been written.
+pub(crate) fn poll_write_buf<W>(w: &mut W, cx: &mut Context, buf: &[u8], pos: &mut usize)
+    -> Poll<io::Result<()>>
+    where W: AsyncWrite + Unpin + ?Sized
+{
+    while *pos < buf.len() {
+        match Pin::new(&mut *w).poll_write(cx, &buf[*pos..]) {
+            Poll::Ready(Ok(0)) => {
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                      "failed to write whole buffer")));
+            }
+            Poll::Ready(Ok(n)) => *pos += n,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending
+        }
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// A future that writes a buffer to an `AsyncWrite` and then flushes it.
+pub(crate) struct WriteAndFlush<W> {
+    w: W,
+    buf: Vec<u8>,
+    pos: usize
+}
+
+impl<W: AsyncWrite + Unpin> WriteAndFlush<W> {
+    pub(crate) fn new(w: W, buf: Vec<u8>) -> WriteAndFlush<W> {
+        WriteAndFlush { w, buf, pos: 0 }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Future for WriteAndFlush<W> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match poll_write_buf(&mut this.w, cx, &this.buf, &mut this.pos) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.w).poll_flush(cx),
+            other => other
+        }
+    }
+}
+
+/// An `AsyncWrite` that accepts at most 3 bytes per call and returns `Pending` every other call,
+/// for testing that partial writes are resumed correctly.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct Trickle {
+    pub(crate) data: Vec<u8>,
+    pub(crate) flushed: bool,
+    ready: bool
+}
+
+#[cfg(test)]
+impl Trickle {
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<()> {
+        self.ready = !self.ready;
+        if self.ready {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+impl AsyncWrite for Trickle {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.poll_ready(cx) {
+            Poll::Ready(()) => {
+                let n = buf.len().min(3);
+                this.data.extend_from_slice(&buf[..n]);
+                this.flushed = false;
+                Poll::Ready(Ok(n))
+            }
+            Poll::Pending => Poll::Pending
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().flushed = true;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Runs `future` to completion on a single-threaded runtime.
+#[cfg(test)]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    ::tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+}
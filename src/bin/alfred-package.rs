@@ -0,0 +1,111 @@
+//! Packages, unpacks, and inspects `.alfredworkflow` files.
+//!
+//! ```text
+//! alfred-package pack <dir> [-o <file>] [--ignore <pattern>]... [--no-ignore-file]
+//! alfred-package unpack <file> <dir>
+//! alfred-package inspect <file>
+//! ```
+
+extern crate alfred;
+
+use alfred::package::{self, Packager, WorkflowInfo};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "\
+usage: alfred-package pack <dir> [-o <file>] [--ignore <pattern>]... [--no-ignore-file]
+       alfred-package unpack <file> <dir>
+       alfred-package inspect <file>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("pack") => pack(&args[1..]),
+        Some("unpack") if args.len() == 3 => unpack(&args[1], &args[2]),
+        Some("inspect") if args.len() == 2 => inspect(&args[1]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => usage()
+    };
+    if let Err(err) = result {
+        eprintln!("alfred-package: {}", err);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn pack(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut dir = None;
+    let mut output = None;
+    let mut patterns = Vec::new();
+    let mut use_ignore_file = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(args.next().unwrap_or_else(|| usage())),
+            "--ignore" => patterns.push(args.next().unwrap_or_else(|| usage())),
+            "--no-ignore-file" => use_ignore_file = false,
+            _ if arg.starts_with('-') || dir.is_some() => usage(),
+            _ => dir = Some(arg)
+        }
+    }
+    let dir = dir.unwrap_or_else(|| usage());
+
+    let mut packager = Packager::new(dir).use_ignore_file(use_ignore_file);
+    for pattern in patterns {
+        packager = packager.ignore(pattern.as_str());
+    }
+    let output = match output {
+        Some(output) => output.clone(),
+        None => packager.file_name()?
+    };
+    packager.write_to(&output)?;
+    print_info(&packager.info()?);
+    println!("Wrote {}", output);
+    Ok(())
+}
+
+fn unpack(file: &str, dir: &str) -> Result<(), Box<dyn Error>> {
+    if Path::new(dir).exists() {
+        return Err(format!("{} already exists", dir).into());
+    }
+    let info = package::unpack(File::open(file)?, dir)?;
+    print_info(&info);
+    println!("Unpacked to {}", dir);
+    Ok(())
+}
+
+fn inspect(file: &str) -> Result<(), Box<dyn Error>> {
+    let info = package::inspect(File::open(file)?)?;
+    print_info(&info);
+    println!("Files:");
+    for file in info.files() {
+        println!("  {}", file);
+    }
+    Ok(())
+}
+
+fn print_info(info: &WorkflowInfo) {
+    println!("Name: {}", info.name());
+    println!("Bundle ID: {}", info.bundle_id().unwrap_or("(none)"));
+    println!("Version: {}", info.version().unwrap_or("(none)"));
+    if !info.variables().is_empty() {
+        println!("Variables:");
+        for (name, value) in info.variables() {
+            if info.dont_export().contains(name) {
+                println!("  {} (not exported)", name);
+            } else {
+                println!("  {} = {}", name, value);
+            }
+        }
+    }
+}
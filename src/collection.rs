@@ -0,0 +1,244 @@
+//! A list of items with the operations script filters usually need
+//!
+//! `Items` wraps a `Vec<Item>` and adds removing items with duplicate uids, sorting, capping the
+//! number of items with a "+N more" summary, and showing a placeholder when there are no results.
+//! The operations consume and return the list so they can be chained, and `Items` dereferences to
+//! a slice of items, so it can be passed to anything that takes `&[Item]`.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # use std::io;
+//! # fn main() {
+//! use alfred::{Item, ItemBuilder, Items};
+//!
+//! let query = "ap";
+//! let items: Items = vec!["banana", "apple", "grape", "apple"].into_iter()
+//!     .filter(|name| name.contains(query))
+//!     .map(|name| ItemBuilder::new(name).uid(name).into_item())
+//!     .collect();
+//! let items = items.dedup_by_uid()
+//!                  .sort_by_title()
+//!                  .truncate(10)
+//!                  .or_placeholder(Item::new(format!("No results for “{}”", query)));
+//! assert_eq!(items.len(), 2);
+//! items.write_json(io::stdout()).unwrap();
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+
+use {json, xml, Item, ItemBuilder};
+
+/// A list of items.
+///
+/// See the module documentation for details.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Items<'a> {
+    items: Vec<Item<'a>>
+}
+
+impl<'a> Items<'a> {
+    /// Returns a new, empty `Items`.
+    pub fn new() -> Items<'a> {
+        Items { items: Vec::new() }
+    }
+
+    /// Appends an item.
+    pub fn push(&mut self, item: Item<'a>) {
+        self.items.push(item);
+    }
+
+    /// Returns the items as a `Vec`.
+    pub fn into_vec(self) -> Vec<Item<'a>> {
+        self.items
+    }
+
+    /// Removes items whose `uid` is the same as an earlier item's.
+    ///
+    /// Items without a `uid` are kept.
+    pub fn dedup_by_uid(mut self) -> Items<'a> {
+        {
+            let mut seen = HashSet::new();
+            self.items.retain(|item| match item.uid {
+                Some(ref uid) => seen.insert(uid.clone()),
+                None => true
+            });
+        }
+        self
+    }
+
+    /// Sorts the items by title, ignoring case.
+    ///
+    /// The sort is stable, so items with the same title keep their order.
+    pub fn sort_by_title(mut self) -> Items<'a> {
+        self.items.sort_by_cached_key(|item| item.title.to_lowercase());
+        self
+    }
+
+    /// Sorts the items with the given comparison function.
+    ///
+    /// The sort is stable, so items that compare equal keep their order.
+    pub fn sort_by<F>(mut self, compare: F) -> Items<'a>
+        where F: FnMut(&Item<'a>, &Item<'a>) -> ::std::cmp::Ordering
+    {
+        self.items.sort_by(compare);
+        self
+    }
+
+    /// Keeps the first `max` items, and if any were removed, appends an item saying how many.
+    ///
+    /// The summary item's title is "+N more". It isn't valid, so actioning it does nothing. Use
+    /// `truncate_with()` to customize it.
+    pub fn truncate(self, max: usize) -> Items<'a> {
+        self.truncate_with(max, |n| {
+            ItemBuilder::new(format!("+{} more", n))
+                        .subtitle("Refine your query to see more results")
+                        .valid(false)
+                        .into_item()
+        })
+    }
+
+    /// Keeps the first `max` items, and if any were removed, appends the item returned by
+    /// `summary` for the number of items removed.
+    pub fn truncate_with<F>(mut self, max: usize, summary: F) -> Items<'a>
+        where F: FnOnce(usize) -> Item<'a>
+    {
+        if self.items.len() > max {
+            let removed = self.items.len() - max;
+            self.items.truncate(max);
+            self.items.push(summary(removed));
+        }
+        self
+    }
+
+    /// Adds `placeholder` if there are no items.
+    ///
+    /// The placeholder is made invalid, so actioning it does nothing.
+    pub fn or_placeholder(mut self, mut placeholder: Item<'a>) -> Items<'a> {
+        if self.items.is_empty() {
+            placeholder.valid = false;
+            self.items.push(placeholder);
+        }
+        self
+    }
+
+    /// Adds a placeholder item with the given title if there are no items.
+    ///
+    /// See `or_placeholder()`.
+    pub fn or_placeholder_title<S: Into<Cow<'a, str>>>(self, title: S) -> Items<'a> {
+        self.or_placeholder(Item::new(title))
+    }
+
+    /// Returns a `json::Builder` for the items, which can be used to add variables.
+    pub fn json_builder(&self) -> json::Builder<'_> {
+        json::Builder::with_items(&self.items)
+    }
+
+    /// Writes a complete JSON document representing the items to the `Write`.
+    ///
+    /// The `Write` is flushed after the JSON document is written.
+    pub fn write_json<W: Write>(&self, w: W) -> io::Result<()> {
+        json::write_items(w, &self.items)
+    }
+
+    /// Writes a complete XML document representing the items to the `Write`.
+    ///
+    /// The `Write` is flushed after the XML document is written.
+    pub fn write_xml<W: Write>(&self, w: W) -> io::Result<()> {
+        xml::write_items(w, &self.items)
+    }
+}
+
+impl<'a> Deref for Items<'a> {
+    type Target = [Item<'a>];
+
+    fn deref(&self) -> &[Item<'a>] {
+        &self.items
+    }
+}
+
+impl<'a> DerefMut for Items<'a> {
+    fn deref_mut(&mut self) -> &mut [Item<'a>] {
+        &mut self.items
+    }
+}
+
+impl<'a> From<Vec<Item<'a>>> for Items<'a> {
+    fn from(items: Vec<Item<'a>>) -> Items<'a> {
+        Items { items }
+    }
+}
+
+impl<'a> From<Items<'a>> for Vec<Item<'a>> {
+    fn from(items: Items<'a>) -> Vec<Item<'a>> {
+        items.items
+    }
+}
+
+impl<'a> FromIterator<Item<'a>> for Items<'a> {
+    fn from_iter<I: IntoIterator<Item=Item<'a>>>(iter: I) -> Items<'a> {
+        Items { items: iter.into_iter().collect() }
+    }
+}
+
+impl<'a> Extend<Item<'a>> for Items<'a> {
+    fn extend<I: IntoIterator<Item=Item<'a>>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for Items<'a> {
+    type Item = Item<'a>;
+    type IntoIter = ::std::vec::IntoIter<Item<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b Items<'a> {
+    type Item = &'b Item<'a>;
+    type IntoIter = ::std::slice::Iter<'b, Item<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+#[test]
+fn test_items() {
+    let item = |title: &'static str, uid: Option<&'static str>| {
+        let mut builder = ItemBuilder::new(title);
+        if let Some(uid) = uid {
+            builder.set_uid(uid);
+        }
+        builder.into_item()
+    };
+    let items: Items = vec![item("b", Some("1")), item("C", None), item("a", Some("2")),
+                            item("b2", Some("1")), item("c", None)].into_iter().collect();
+    let titles = |items: &Items| items.iter().map(|i| i.title.to_string()).collect::<Vec<_>>();
+
+    let items = items.dedup_by_uid();
+    assert_eq!(titles(&items), ["b", "C", "a", "c"]);
+    let items = items.sort_by_title();
+    assert_eq!(titles(&items), ["a", "b", "C", "c"]);
+    let truncated = items.clone().truncate(2);
+    assert_eq!(titles(&truncated), ["a", "b", "+2 more"]);
+    assert!(!truncated[2].valid);
+    assert_eq!(items.clone().truncate(4), items);
+
+    let mut output = Vec::new();
+    Items::new().or_placeholder_title("No results").write_json(&mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(),
+               r#"{"items":[{"title":"No results","valid":false}]}"#);
+    let mut output = Vec::new();
+    items.clone().or_placeholder_title("No results").write_xml(&mut output).unwrap();
+    assert!(!String::from_utf8(output).unwrap().contains("No results"));
+    assert_eq!(items.json_builder().variable("k", "v").into_json()["variables"]["k"], "v");
+}
@@ -0,0 +1,313 @@
+//! Parsing of the theme colors Alfred exposes to workflows
+//!
+//! Alfred passes theme colors in the `alfred_theme_background` and
+//! `alfred_theme_selection_background` environment variables as CSS color strings, usually of the
+//! form `rgba(r,g,b,a)`. The `Color` type parses these along with the other common CSS color
+//! forms: `rgb()`, `hsl()`/`hsla()`, hex colors, and the basic named colors.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! use alfred::color::Color;
+//!
+//! # fn main() {
+//! let color: Color = "rgba(255,255,255,0.98)".parse().unwrap();
+//! assert_eq!(color, Color::new(255, 255, 255, 0.98));
+//! assert!(!color.is_dark());
+//!
+//! let color: Color = "#1e1e1e".parse().unwrap();
+//! assert!(color.is_dark());
+//! # }
+//! ```
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An RGBA color.
+///
+/// The color channels are 8-bit sRGB values and alpha is in the range `0.0...1.0`.
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct Color {
+    /// The red channel.
+    pub red: u8,
+    /// The green channel.
+    pub green: u8,
+    /// The blue channel.
+    pub blue: u8,
+    /// The alpha channel, from `0.0` (transparent) to `1.0` (opaque).
+    pub alpha: f32
+}
+
+impl Color {
+    /// Returns a new `Color` with the given channels.
+    ///
+    /// `alpha` is clamped to the range `0.0...1.0`.
+    pub fn new(red: u8, green: u8, blue: u8, alpha: f32) -> Color {
+        Color { red, green, blue, alpha: clamp(alpha, 0.0, 1.0) }
+    }
+
+    /// Returns a new opaque `Color` with the given channels.
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color::new(red, green, blue, 1.0)
+    }
+
+    /// Returns the relative luminance of the color, as defined by WCAG 2.0.
+    ///
+    /// The result is in the range `0.0` (black) to `1.0` (white). Alpha is ignored.
+    pub fn relative_luminance(&self) -> f32 {
+        fn linearize(c: u8) -> f32 {
+            let c = f32::from(c) / 255.0;
+            if c <= 0.039_28 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        0.2126 * linearize(self.red) + 0.7152 * linearize(self.green)
+            + 0.0722 * linearize(self.blue)
+    }
+
+    /// Returns the WCAG 2.0 contrast ratio between this color and `other`.
+    ///
+    /// The result is in the range `1.0` (no contrast) to `21.0` (black on white).
+    pub fn contrast_ratio(&self, other: &Color) -> f32 {
+        let (a, b) = (self.relative_luminance(), other.relative_luminance());
+        let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+        (lighter + 0.05) / (darker + 0.05)
+    }
+
+    /// Returns `true` if the color is dark.
+    ///
+    /// A color is considered dark if white text on it has more contrast than black text. This is
+    /// the right test for picking a light or dark foreground, such as an icon variant, to draw on
+    /// top of the color. Alpha is ignored.
+    pub fn is_dark(&self) -> bool {
+        // The luminance at which the contrast against white and black is equal.
+        self.relative_luminance() < 0.179
+    }
+
+    /// Returns `true` if the color is light.
+    ///
+    /// This is the inverse of `is_dark()`.
+    pub fn is_light(&self) -> bool {
+        !self.is_dark()
+    }
+
+    /// Returns the color composited over an opaque `background`.
+    pub fn over(&self, background: &Color) -> Color {
+        let blend = |fg: u8, bg: u8| {
+            (f32::from(fg) * self.alpha + f32::from(bg) * (1.0 - self.alpha)).round() as u8
+        };
+        Color::rgb(blend(self.red, background.red),
+                   blend(self.green, background.green),
+                   blend(self.blue, background.blue))
+    }
+}
+
+impl fmt::Display for Color {
+    /// Formats the color the same way Alfred does, as `rgba(r,g,b,a)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rgba({},{},{},{})", self.red, self.green, self.blue, self.alpha)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Color, ParseColorError> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex).ok_or(ParseColorError(()));
+        }
+        let lower = s.to_ascii_lowercase();
+        if let Some(open) = lower.find('(') {
+            if !lower.ends_with(')') {
+                return Err(ParseColorError(()));
+            }
+            let name = lower[..open].trim();
+            let args = &lower[open+1..lower.len()-1];
+            return match name {
+                "rgb" | "rgba" => parse_rgb(args),
+                "hsl" | "hsla" => parse_hsl(args),
+                _ => None
+            }.ok_or(ParseColorError(()));
+        }
+        named_color(&lower).ok_or(ParseColorError(()))
+    }
+}
+
+/// An error returned when parsing a `Color` fails.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ParseColorError(());
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid color syntax")
+    }
+}
+
+impl error::Error for ParseColorError {}
+
+fn clamp(x: f32, min: f32, max: f32) -> f32 {
+    if x.is_nan() { min } else { x.max(min).min(max) }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i+1], 16).ok().map(|d| d * 17);
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i+2], 16).ok();
+    match hex.len() {
+        3 => Some(Color::rgb(digit(0)?, digit(1)?, digit(2)?)),
+        4 => Some(Color::new(digit(0)?, digit(1)?, digit(2)?, f32::from(digit(3)?) / 255.0)),
+        6 => Some(Color::rgb(pair(0)?, pair(2)?, pair(4)?)),
+        8 => Some(Color::new(pair(0)?, pair(2)?, pair(4)?, f32::from(pair(6)?) / 255.0)),
+        _ => None
+    }
+}
+
+/// Splits the arguments of a CSS color function.
+///
+/// Accepts both the legacy comma-separated syntax `r, g, b, a` and the modern space-separated
+/// syntax `r g b / a`.
+fn split_args(args: &str) -> Option<(Vec<&str>, Option<&str>)> {
+    if args.contains(',') {
+        let mut parts: Vec<&str> = args.split(',').map(str::trim).collect();
+        if parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+        let alpha = if parts.len() == 4 { parts.pop() } else { None };
+        Some((parts, alpha))
+    } else {
+        let mut halves = args.splitn(2, '/');
+        let parts = halves.next()?.split_whitespace().collect();
+        let alpha = match halves.next() {
+            Some(a) if a.trim().is_empty() => return None,
+            Some(a) => Some(a.trim()),
+            None => None
+        };
+        Some((parts, alpha))
+    }
+}
+
+fn parse_number(s: &str) -> Option<f32> {
+    s.parse::<f32>().ok().filter(|x| x.is_finite())
+}
+
+fn parse_alpha(s: Option<&str>) -> Option<f32> {
+    match s {
+        None => Some(1.0),
+        Some(s) => match s.strip_suffix('%') {
+            Some(pct) => parse_number(pct).map(|x| clamp(x / 100.0, 0.0, 1.0)),
+            None => parse_number(s).map(|x| clamp(x, 0.0, 1.0))
+        }
+    }
+}
+
+fn parse_rgb(args: &str) -> Option<Color> {
+    let (parts, alpha) = split_args(args)?;
+    if parts.len() != 3 {
+        return None;
+    }
+    let channel = |s: &str| -> Option<u8> {
+        let x = match s.strip_suffix('%') {
+            Some(pct) => parse_number(pct)? * 255.0 / 100.0,
+            None => parse_number(s)?
+        };
+        Some(clamp(x, 0.0, 255.0).round() as u8)
+    };
+    Some(Color::new(channel(parts[0])?, channel(parts[1])?, channel(parts[2])?,
+                    parse_alpha(alpha)?))
+}
+
+fn parse_hsl(args: &str) -> Option<Color> {
+    let (parts, alpha) = split_args(args)?;
+    if parts.len() != 3 {
+        return None;
+    }
+    let hue = parse_number(parts[0].trim_end_matches("deg"))?;
+    let percent = |s: &str| parse_number(s.strip_suffix('%')?).map(|x| clamp(x / 100.0, 0.0, 1.0));
+    let (s, l) = (percent(parts[1])?, percent(parts[2])?);
+
+    // https://www.w3.org/TR/css-color-3/#hsl-color
+    let h = ((hue % 360.0) + 360.0) % 360.0 / 360.0;
+    let m2 = if l <= 0.5 { l * (s + 1.0) } else { l + s - l * s };
+    let m1 = l * 2.0 - m2;
+    let hue_to_rgb = |h: f32| {
+        let h = if h < 0.0 { h + 1.0 } else if h > 1.0 { h - 1.0 } else { h };
+        let x = if h * 6.0 < 1.0 {
+            m1 + (m2 - m1) * h * 6.0
+        } else if h * 2.0 < 1.0 {
+            m2
+        } else if h * 3.0 < 2.0 {
+            m1 + (m2 - m1) * (2.0 / 3.0 - h) * 6.0
+        } else {
+            m1
+        };
+        (clamp(x, 0.0, 1.0) * 255.0).round() as u8
+    };
+    Some(Color::new(hue_to_rgb(h + 1.0 / 3.0), hue_to_rgb(h), hue_to_rgb(h - 1.0 / 3.0),
+                    parse_alpha(alpha)?))
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    Some(match name {
+        "transparent" => Color::new(0, 0, 0, 0.0),
+        "black" => Color::rgb(0, 0, 0),
+        "silver" => Color::rgb(192, 192, 192),
+        "gray" | "grey" => Color::rgb(128, 128, 128),
+        "white" => Color::rgb(255, 255, 255),
+        "maroon" => Color::rgb(128, 0, 0),
+        "red" => Color::rgb(255, 0, 0),
+        "purple" => Color::rgb(128, 0, 128),
+        "fuchsia" | "magenta" => Color::rgb(255, 0, 255),
+        "green" => Color::rgb(0, 128, 0),
+        "lime" => Color::rgb(0, 255, 0),
+        "olive" => Color::rgb(128, 128, 0),
+        "yellow" => Color::rgb(255, 255, 0),
+        "navy" => Color::rgb(0, 0, 128),
+        "blue" => Color::rgb(0, 0, 255),
+        "teal" => Color::rgb(0, 128, 128),
+        "aqua" | "cyan" => Color::rgb(0, 255, 255),
+        _ => return None
+    })
+}
+
+#[test]
+fn test_parse() {
+    fn parse(s: &str) -> Option<Color> {
+        s.parse().ok()
+    }
+    assert_eq!(parse("rgba(255,255,255,0.98)"), Some(Color::new(255, 255, 255, 0.98)));
+    assert_eq!(parse(" RGBA( 30, 30, 30, 1.00 ) "), Some(Color::rgb(30, 30, 30)));
+    assert_eq!(parse("rgb(100%, 50%, 0%)"), Some(Color::rgb(255, 128, 0)));
+    assert_eq!(parse("rgb(255 0 0 / 50%)"), Some(Color::new(255, 0, 0, 0.5)));
+    assert_eq!(parse("#fff"), Some(Color::rgb(255, 255, 255)));
+    assert_eq!(parse("#11223380"), Some(Color::new(0x11, 0x22, 0x33, 128.0 / 255.0)));
+    assert_eq!(parse("hsl(120, 100%, 25%)"), Some(Color::rgb(0, 128, 0)));
+    assert_eq!(parse("hsla(0deg 0% 100% / 0.5)"), Some(Color::new(255, 255, 255, 0.5)));
+    assert_eq!(parse("White"), Some(Color::rgb(255, 255, 255)));
+    assert_eq!(parse("rgba(1,2,3"), None);
+    assert_eq!(parse("rgb(1,2)"), None);
+    assert_eq!(parse("rgb(1,,2,3)"), None);
+    assert_eq!(parse("#12345"), None);
+    assert_eq!(parse("#ggg"), None);
+    assert_eq!(parse("chartreuse-ish"), None);
+}
+
+#[test]
+fn test_luminance() {
+    let white = Color::rgb(255, 255, 255);
+    let black = Color::rgb(0, 0, 0);
+    assert_eq!(white.relative_luminance(), 1.0);
+    assert_eq!(black.relative_luminance(), 0.0);
+    assert!((white.contrast_ratio(&black) - 21.0).abs() < 1e-4);
+    assert!(black.is_dark() && white.is_light());
+    assert!(Color::rgb(0x1e, 0x1e, 0x1e).is_dark());
+    assert!(Color::rgb(0xe5, 0xe5, 0xe5).is_light());
+    assert_eq!(Color::new(0, 0, 0, 0.5).over(&white), Color::rgb(128, 128, 128));
+    assert_eq!(Color::new(1, 2, 3, 0.5).to_string(), "rgba(1,2,3,0.5)");
+}
@@ -0,0 +1,365 @@
+//! Typed workflow configuration from environment variables
+//!
+//! Workflow variables configured by the user in Alfred are passed to scripts as environment
+//! variables, always as strings. This module deserializes a `serde` type from those variables,
+//! coercing the strings to the types of the fields:
+//!
+//! * `bool` accepts `1`/`0`, `true`/`false`, `yes`/`no` and `on`/`off` (case insensitive). An
+//!   empty value is `false`.
+//! * Integers and floats are parsed from the trimmed value.
+//! * `Option<T>` is `None` if the variable is missing or empty.
+//! * Sequences are split on newlines if the value contains any, and on commas otherwise. Each
+//!   element is trimmed and empty elements are skipped.
+//! * Unit enum variants are matched by name.
+//! * Paths are read verbatim. Use `tilde_path` with `#[serde(deserialize_with)]` to expand a
+//!   leading `~`.
+//!
+//! Alfred's own `alfred_*` variables are not visible to the deserializer; use `alfred::env` to
+//! read those. Other variables in the process environment, such as `PATH`, are visible, so
+//! `#[serde(deny_unknown_fields)]` should not be used.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # #[macro_use] extern crate serde_derive;
+//! use alfred::env::Environment;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     api_key: String,
+//!     #[serde(default)]
+//!     max_results: Option<usize>,
+//!     show_hidden: bool,
+//!     tags: Vec<String>,
+//! }
+//!
+//! # fn main() {
+//! // Normally this would be `alfred::config::from_env()`.
+//! let env = Environment::from_map(vec![
+//!     ("api_key", "secret"),
+//!     ("show_hidden", "1"),
+//!     ("tags", "rust, alfred"),
+//!     ("alfred_workflow_name", "Example"),
+//! ]);
+//! let config: Config = alfred::config::from_environment(&env).unwrap();
+//! assert_eq!(config.api_key, "secret");
+//! assert_eq!(config.max_results, None);
+//! assert!(config.show_hidden);
+//! assert_eq!(config.tags, vec!["rust", "alfred"]);
+//!
+//! let env = Environment::from_map(vec![("show_hidden", "maybe")]);
+//! let err = alfred::config::from_environment::<Config>(&env).err().unwrap();
+//! assert_eq!(err.variable(), Some("show_hidden"));
+//! # }
+//! ```
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use std::error;
+use std::fmt;
+use std::path::PathBuf;
+
+use env::{Environment, UserVars};
+
+/// Deserializes `T` from the workflow variables in the process environment.
+pub fn from_env<T: DeserializeOwned>() -> Result<T, Error> {
+    from_environment(&Environment::from_env())
+}
+
+/// Deserializes `T` from the workflow variables in `env`.
+pub fn from_environment<'de, T: Deserialize<'de>>(env: &'de Environment) -> Result<T, Error> {
+    T::deserialize(EnvDeserializer { vars: env.user_vars() })
+}
+
+/// Deserializes a path, expanding a leading `~` to the user's home directory.
+///
+/// This is meant to be used as `#[serde(deserialize_with = "alfred::config::tilde_path")]`.
+pub fn tilde_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Ok(expand_tilde(&s))
+}
+
+pub(crate) fn expand_tilde(s: &str) -> PathBuf {
+    let rest = if s == "~" {
+        ""
+    } else if let Some(rest) = s.strip_prefix("~/") {
+        rest
+    } else {
+        return PathBuf::from(s);
+    };
+    match ::std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(rest),
+        None => PathBuf::from(s)
+    }
+}
+
+/// An error produced while deserializing workflow configuration.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Error {
+    variable: Option<String>,
+    message: String
+}
+
+impl Error {
+    /// Returns the name of the variable that caused the error, if known.
+    pub fn variable(&self) -> Option<&str> {
+        self.variable.as_deref()
+    }
+
+    /// Returns the error message, without the variable name.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn with_variable(mut self, name: &str) -> Error {
+        if self.variable.is_none() {
+            self.variable = Some(name.to_owned());
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.variable {
+            Some(ref name) => write!(f, "workflow variable `{}`: {}", name, self.message),
+            None => f.write_str(&self.message)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error { variable: None, message: msg.to_string() }
+    }
+
+    fn missing_field(field: &'static str) -> Error {
+        Error { variable: Some(field.to_owned()), message: "variable is not set".to_owned() }
+    }
+}
+
+struct EnvDeserializer<'de> {
+    vars: UserVars<'de>
+}
+
+impl<'de> Deserializer<'de> for EnvDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(VarsAccess { vars: self.vars, value: None })
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+                                                  -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct VarsAccess<'de> {
+    vars: UserVars<'de>,
+    value: Option<(&'de str, &'de str)>
+}
+
+impl<'de> de::MapAccess<'de> for VarsAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K)
+                                                 -> Result<Option<K::Value>, Error> {
+        match self.vars.next() {
+            Some((name, value)) => {
+                self.value = Some((name, value));
+                seed.deserialize(de::value::BorrowedStrDeserializer::new(name)).map(Some)
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, value) = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(VarDeserializer { value }).map_err(|e| e.with_variable(name))
+    }
+}
+
+/// Deserializes a single variable value.
+struct VarDeserializer<'de> {
+    value: &'de str
+}
+
+impl<'de> VarDeserializer<'de> {
+    fn parse<T: ::std::str::FromStr>(&self, expected: &str) -> Result<T, Error> {
+        self.value.trim().parse().map_err(|_| {
+            de::Error::custom(format_args!("expected {}, found `{}`", expected, self.value))
+        })
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $expected:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse($expected)?)
+            }
+        )*
+    }
+}
+
+impl<'de> Deserializer<'de> for VarDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &*self.value.trim().to_ascii_lowercase() {
+            "1" | "true" | "yes" | "on" => visitor.visit_bool(true),
+            "" | "0" | "false" | "no" | "off" => visitor.visit_bool(false),
+            _ => Err(de::Error::custom(format_args!("expected a boolean such as `1` or `true`, \
+                                                     found `{}`", self.value)))
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8, "an integer";
+        deserialize_i16 => visit_i16, "an integer";
+        deserialize_i32 => visit_i32, "an integer";
+        deserialize_i64 => visit_i64, "an integer";
+        deserialize_i128 => visit_i128, "an integer";
+        deserialize_u8 => visit_u8, "a non-negative integer";
+        deserialize_u16 => visit_u16, "a non-negative integer";
+        deserialize_u32 => visit_u32, "a non-negative integer";
+        deserialize_u64 => visit_u64, "a non-negative integer";
+        deserialize_u128 => visit_u128, "a non-negative integer";
+        deserialize_f32 => visit_f32, "a number";
+        deserialize_f64 => visit_f64, "a number";
+        deserialize_char => visit_char, "a single character";
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.trim().is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+                                                  -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let sep = if self.value.contains('\n') { '\n' } else { ',' };
+        let elements = self.value.split(sep)
+                                 .map(str::trim)
+                                 .filter(|s| !s.is_empty())
+                                 .map(|value| VarDeserializer { value });
+        visitor.visit_seq(de::value::SeqDeserializer::new(elements))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V)
+                                         -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str,
+                                         _variants: &'static [&'static str], visitor: V)
+                                        -> Result<V::Value, Error> {
+        visitor.visit_enum(de::value::BorrowedStrDeserializer::new(self.value.trim()))
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for VarDeserializer<'de> {
+    type Deserializer = VarDeserializer<'de>;
+
+    fn into_deserializer(self) -> VarDeserializer<'de> {
+        self
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug,PartialEq,Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    Fast,
+    Thorough
+}
+
+#[cfg(test)]
+#[derive(Debug,PartialEq,Deserialize)]
+struct TestConfig {
+    name: String,
+    verbose: bool,
+    limit: u32,
+    ratio: Option<f64>,
+    ids: Vec<i64>,
+    lines: Vec<String>,
+    mode: Mode,
+    #[serde(default)]
+    missing: Option<String>,
+    #[serde(deserialize_with = "tilde_path")]
+    dir: PathBuf
+}
+
+#[test]
+fn test_from_environment() {
+    let mut env = Environment::from_map(vec![
+        ("name", "Example"),
+        ("verbose", "TRUE"),
+        ("limit", " 25 "),
+        ("ratio", ""),
+        ("ids", "1, 2,,3"),
+        ("lines", "first, line\nsecond line\n"),
+        ("mode", "thorough"),
+        ("dir", "/tmp/example"),
+        ("alfred_workflow_name", "Ignored"),
+    ]);
+    let config: TestConfig = from_environment(&env).unwrap();
+    assert_eq!(config, TestConfig {
+        name: "Example".to_owned(),
+        verbose: true,
+        limit: 25,
+        ratio: None,
+        ids: vec![1, 2, 3],
+        lines: vec!["first, line".to_owned(), "second line".to_owned()],
+        mode: Mode::Thorough,
+        missing: None,
+        dir: PathBuf::from("/tmp/example")
+    });
+
+    env.set_var("limit", "lots");
+    let err = from_environment::<TestConfig>(&env).unwrap_err();
+    assert_eq!(err.to_string(),
+               "workflow variable `limit`: expected a non-negative integer, found `lots`");
+
+    env.set_var("limit", "25");
+    env.set_var("mode", "sloppy");
+    let err = from_environment::<TestConfig>(&env).unwrap_err();
+    assert_eq!(err.variable(), Some("mode"));
+
+    env.set_var("mode", "fast");
+    env.remove_var("name");
+    let err = from_environment::<TestConfig>(&env).unwrap_err();
+    assert_eq!(err.to_string(), "workflow variable `name`: variable is not set");
+
+    // Alfred's own variables aren't visible as user variables.
+    env.set_var("name", "Example");
+    env.remove_var("verbose");
+    env.set_var("alfred_verbose", "1");
+    assert_eq!(from_environment::<TestConfig>(&env).unwrap_err().variable(), Some("verbose"));
+}
@@ -0,0 +1,388 @@
+//! Panic handling that still produces valid script filter output
+//!
+//! If a workflow binary panics, Alfred receives either nothing or a truncated document, and the
+//! user is left looking at an empty list. `install()` sets a panic hook that writes a well-formed
+//! script filter response containing a single error item, and saves a crash report with a
+//! backtrace under the workflow data directory.
+//!
+//! The hook needs to know what has already been written to stdout so it doesn't emit a second
+//! document. Output should therefore be written through `alfred::crash::stdout()`, which tracks
+//! this. `alfred::runner::Runner` does so automatically.
+//!
+//! * If nothing has been written yet, the hook writes a complete document.
+//! * If an XML document has been started but not finished, the hook appends the error item and
+//!   the closing `</items>`, and any output written afterwards (such as the footer written when
+//!   an `XMLWriter` is dropped during unwinding) is discarded.
+//! * If a JSON document has been started, the hook writes nothing, as there's no way to finish a
+//!   partial JSON document with an error item.
+//! * If a complete document has already been written, the hook writes nothing.
+//!
+//! Only panics on the thread that called `install()` are handled this way. A panic on another
+//! thread may be caught, for example by joining the thread, and the output written afterwards is
+//! still needed, so those panics are only passed to the previous hook.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! # use std::io;
+//! #
+//! fn main() {
+//!     alfred::crash::install(alfred::runner::Format::Json);
+//!
+//!     let item = alfred::Item::new("Item 1");
+//!     let result = alfred::json::write_items(alfred::crash::stdout(), &[item]);
+//!     if let Err(err) = result {
+//!         eprintln!("Error writing items: {}", err);
+//!     }
+//! }
+//! ```
+
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use env::Environment;
+use runner::{Format, ERROR_ICON};
+use {json, xml, Icon, Item, ItemBuilder};
+
+/// What has been written to stdout through `Stdout`.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+enum Output {
+    /// Nothing has been written.
+    Empty,
+    /// Part of a document has been written.
+    Partial,
+    /// A complete document has been written.
+    Complete,
+    /// The panic hook has finished the document, and further output is discarded.
+    Closed
+}
+
+struct State {
+    output: Output,
+    /// The last bytes written, used to detect the end of an XML document.
+    tail: Vec<u8>
+}
+
+static STATE: Mutex<State> = Mutex::new(State { output: Output::Empty, tail: Vec::new() });
+
+thread_local! {
+    /// Whether this thread holds the lock on `STATE` while writing to stdout.
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How long the panic hook waits for another thread to finish writing to stdout.
+const LOCK_TIMEOUT: Duration = Duration::from_millis(50);
+
+const XML_FOOTER: &[u8] = b"</items>\n";
+
+fn lock_state() -> MutexGuard<'static, State> {
+    // A panic while holding the lock can't leave the state inconsistent, so ignore poisoning.
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns a handle to stdout that records what has been written for the panic hook.
+pub fn stdout() -> Stdout {
+    Stdout { _priv: () }
+}
+
+/// A handle to stdout that records what has been written for the panic hook.
+///
+/// This is returned by `alfred::crash::stdout()`.
+#[derive(Debug)]
+pub struct Stdout {
+    _priv: ()
+}
+
+impl Stdout {
+    /// Records that a complete document has been written.
+    ///
+    /// XML documents are detected as complete when the `</items>` footer is written, so this only
+    /// needs to be called when writing XML some other way. The panic hook writes nothing once any
+    /// JSON has been written, so it makes no difference for JSON.
+    pub fn mark_complete(&self) {
+        let mut state = lock_state();
+        if state.output != Output::Closed {
+            state.output = Output::Complete;
+        }
+    }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = lock_state();
+        if state.output == Output::Closed {
+            return Ok(buf.len());
+        }
+        WRITING.with(|w| w.set(true));
+        let result = io::stdout().write(buf);
+        WRITING.with(|w| w.set(false));
+        let n = result?;
+        if n > 0 && state.output == Output::Empty {
+            state.output = Output::Partial;
+        }
+        state.tail.extend_from_slice(&buf[..n]);
+        let excess = state.tail.len().saturating_sub(XML_FOOTER.len());
+        state.tail.drain(..excess);
+        if state.tail == XML_FOOTER {
+            state.output = Output::Complete;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if lock_state().output == Output::Closed {
+            return Ok(());
+        }
+        io::stdout().flush()
+    }
+}
+
+/// Installs a panic hook that writes an error item in the given format.
+///
+/// This should be called from the main thread. Panics on other threads don't write anything; see
+/// the module documentation for details.
+///
+/// The previously installed hook still runs first, so the panic message continues to appear on
+/// stderr and in Alfred's debugger.
+///
+/// If the workflow has a data directory, a crash report including a backtrace is written to a
+/// `crashes` directory inside it, and the error item refers to it.
+pub fn install(format: Format) {
+    let previous = panic::take_hook();
+    let main_thread = thread::current().id();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if thread::current().id() != main_thread {
+            return;
+        }
+
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(s) => (*s).to_owned(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Box<dyn Any>".to_owned()
+            }
+        };
+        let location = info.location().map(|l| l.to_string());
+        let env = Environment::from_env();
+        let report = env.workflow_data().and_then(|dir| {
+            let report = crash_report(&env, &message, location.as_deref(),
+                                      &Backtrace::force_capture().to_string());
+            write_crash_report(&dir, &report).ok()
+        });
+
+        let mut state = match lock_state_for_hook() {
+            Some(state) => state,
+            None => return
+        };
+        let output = panic_output(format, state.output, &message, report.as_deref());
+        if !output.is_empty() {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let _ = stdout.write_all(&output).and_then(|_| stdout.flush());
+            state.output = Output::Closed;
+        }
+    }));
+}
+
+/// Locks the state from the panic hook.
+///
+/// Returns `None` if the panic happened on this thread while writing to stdout, as the lock is
+/// still held, or if another thread is still writing after `LOCK_TIMEOUT`.
+fn lock_state_for_hook() -> Option<MutexGuard<'static, State>> {
+    if WRITING.with(Cell::get) {
+        return None;
+    }
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match STATE.try_lock() {
+            Ok(state) => return Some(state),
+            Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(TryLockError::WouldBlock) => return None
+        }
+    }
+}
+
+/// Returns the bytes the panic hook should write, given what has been written so far.
+fn panic_output(format: Format, output: Output, message: &str, report: Option<&Path>) -> Vec<u8> {
+    let item = panic_item(message, report);
+    let mut buf = Vec::new();
+    match (format, output) {
+        (_, Output::Complete) | (_, Output::Closed) => {}
+        // A JSON document that has been started, whether or not it's complete, can't take another
+        // item.
+        (Format::Json, Output::Partial) => {}
+        (Format::Json, Output::Empty) => {
+            let _ = json::write_items(&mut buf, &[item]);
+        }
+        (Format::Xml, Output::Empty) => {
+            let _ = xml::write_items(&mut buf, &[item]);
+        }
+        (Format::Xml, Output::Partial) => {
+            let _ = item.write_xml(&mut buf, 1);
+            buf.extend_from_slice(XML_FOOTER);
+        }
+    }
+    buf
+}
+
+fn panic_item(message: &str, report: Option<&Path>) -> Item<'static> {
+    let subtitle = match report {
+        Some(path) => format!("A crash report was saved to {}", path.display()),
+        None => "Open the workflow debugger for details".to_owned()
+    };
+    ItemBuilder::new(format!("Workflow crashed: {}", message))
+                .subtitle(subtitle)
+                .icon(Icon::Path(Cow::Borrowed(ERROR_ICON)))
+                .valid(false)
+                .text_copy(message.to_owned())
+                .text_large_type(message.to_owned())
+                .into_item()
+}
+
+fn crash_report(env: &Environment, message: &str, location: Option<&str>, backtrace: &str)
+               -> String {
+    let or_unknown = |s: Option<String>| s.unwrap_or_else(|| "unknown".to_owned());
+    format!("Workflow: {} {} ({})\nAlfred: {} (build {})\n\nPanic: {}\nLocation: {}\n\n\
+             Backtrace:\n{}\n",
+            or_unknown(env.workflow_name()), or_unknown(env.workflow_version()),
+            or_unknown(env.workflow_bundle_id()), or_unknown(env.version()),
+            or_unknown(env.version_build().map(|b| b.to_string())),
+            message, location.unwrap_or("unknown"), backtrace)
+}
+
+/// Writes the crash report to a new file in `<data_dir>/crashes` and returns its path.
+fn write_crash_report(data_dir: &Path, report: &str) -> io::Result<PathBuf> {
+    let dir = data_dir.join("crashes");
+    fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = dir.join(format!("crash-{}-{}.txt", timestamp, ::std::process::id()));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// Runs `f` with the panic hook installed in a child process running only the test `name`, and
+/// returns the child's stdout. In the child process itself, returns `None` after running `f`.
+///
+/// The panic hook is process-wide and writes to the real stdout, so it can't be tested in the
+/// test process.
+#[cfg(test)]
+pub(crate) fn run_with_hook<F: FnOnce()>(name: &str, format: Format, f: F) -> Option<String> {
+    use std::process::Command;
+
+    const CHILD: &str = "ALFRED_RS_CRASH_TEST";
+    if ::std::env::var_os(CHILD).is_some_and(|v| v == name) {
+        install(format);
+        f();
+        return None;
+    }
+    let output = Command::new(::std::env::current_exe().unwrap())
+                         .args([name, "--exact", "--nocapture", "--test-threads=1"])
+                         .env(CHILD, name)
+                         .output()
+                         .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Returns the script filter document in the output of `run_with_hook()`, which also contains the
+/// output of the test harness.
+#[cfg(test)]
+pub(crate) fn json_document(output: &str) -> ::serde_json::Value {
+    let start = output.find("{\"items\"").expect("no document written");
+    assert_eq!(output.matches("{\"items\"").count(), 1, "more than one document written");
+    let mut documents = ::serde_json::Deserializer::from_str(&output[start..]).into_iter();
+    documents.next().unwrap().unwrap()
+}
+
+#[test]
+fn test_panic_on_other_thread() {
+    let output = run_with_hook("crash::test_panic_on_other_thread", Format::Json, || {
+        assert!(thread::spawn(|| panic!("boom")).join().is_err());
+        json::write_items(stdout(), &[Item::new("Result")]).unwrap();
+    });
+    if let Some(output) = output {
+        let document = json_document(&output);
+        assert_eq!(document["items"].as_array().unwrap().len(), 1);
+        assert_eq!(document["items"][0]["title"], "Result");
+    }
+}
+
+#[test]
+fn test_panic_on_main_thread() {
+    let output = run_with_hook("crash::test_panic_on_main_thread", Format::Json, || {
+        // The panic would fail the test, so catch it once the hook has run.
+        assert!(panic::catch_unwind(|| panic!("boom")).is_err());
+        json::write_items(stdout(), &[Item::new("Result")]).unwrap();
+    });
+    if let Some(output) = output {
+        let document = json_document(&output);
+        assert_eq!(document["items"][0]["title"], "Workflow crashed: boom");
+    }
+}
+
+#[test]
+fn test_panic_while_other_thread_writes() {
+    let name = "crash::test_panic_while_other_thread_writes";
+    let output = run_with_hook(name, Format::Json, || {
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let writer = thread::spawn(move || {
+            let _state = lock_state();
+            tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(30));
+        });
+        rx.recv().unwrap();
+        assert!(panic::catch_unwind(|| panic!("boom")).is_err());
+        writer.join().unwrap();
+    });
+    if let Some(output) = output {
+        let document = json_document(&output);
+        assert_eq!(document["items"][0]["title"], "Workflow crashed: boom");
+    }
+}
+
+#[test]
+fn test_panic_output() {
+    let report = Path::new("/data/crashes/crash-1.txt");
+
+    let output = panic_output(Format::Json, Output::Empty, "boom", Some(report));
+    let output: ::serde_json::Value = ::serde_json::from_slice(&output).unwrap();
+    assert_eq!(output["items"][0]["title"], "Workflow crashed: boom");
+    assert_eq!(output["items"][0]["subtitle"],
+               "A crash report was saved to /data/crashes/crash-1.txt");
+    assert_eq!(output["items"][0]["valid"], false);
+    assert!(panic_output(Format::Json, Output::Partial, "boom", None).is_empty());
+    assert!(panic_output(Format::Json, Output::Complete, "boom", None).is_empty());
+
+    let output = String::from_utf8(panic_output(Format::Xml, Output::Empty, "boom", None)).unwrap();
+    assert!(output.starts_with("<?xml"));
+    assert!(output.ends_with("</items>\n"));
+    let output = String::from_utf8(panic_output(Format::Xml, Output::Partial, "boom", None)).unwrap();
+    assert!(output.starts_with("    <item valid=\"no\">"));
+    assert!(output.ends_with("</item>\n</items>\n"));
+    assert!(panic_output(Format::Xml, Output::Complete, "boom", None).is_empty());
+}
+
+#[test]
+fn test_crash_report() {
+    let dir = ::std::env::temp_dir().join(format!("alfred-crash-test-{}", ::std::process::id()));
+    let env = Environment::from_map(vec![("alfred_workflow_name", "Example")]);
+    let report = crash_report(&env, "boom", Some("src/main.rs:1:1"), "<backtrace>");
+    assert!(report.starts_with("Workflow: Example unknown (unknown)\n"));
+    let path = write_crash_report(&dir, &report).unwrap();
+    assert!(path.starts_with(dir.join("crashes")));
+    assert_eq!(fs::read_to_string(&path).unwrap(), report);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,539 @@
+//! Producing script filter results within a time budget
+//!
+//! Alfred users notice any delay of more than a couple of hundred milliseconds. A `Deadline`
+//! runs a set of item producers in parallel and waits for them until its budget runs out. The
+//! items of the producers that finished are written out, followed by a "Loading…" item, and the
+//! output asks Alfred to rerun the script filter shortly afterwards.
+//!
+//! Finished producers are stored in the workflow cache directory under a key, which is usually
+//! the query, so a rerun shows them again without running them. The producers that didn't finish
+//! are handed to a background process, which is the running executable started again with the
+//! same arguments and an extra environment variable. In that process, `run()` runs the unfinished
+//! producers to completion, stores their items, and returns an `Output` for which `is_worker()`
+//! is `true`. The program should then exit without writing anything. This means the code leading
+//! up to `run()` must be cheap and free of side effects.
+//!
+//! A producer that panics is shown as an error item, built with `alfred::runner::error_item()`,
+//! after the results. The failure is stored like a result, so the producer isn't run again until
+//! the stored results expire.
+//!
+//! Stored items are written out exactly as they were produced, so they don't need to be parsed
+//! back into `Item`s, but the output is always JSON.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! # use std::io;
+//! # fn search_files(_: &str) -> Vec<alfred::Item<'static>> { vec![] }
+//! # fn search_web(_: &str) -> Vec<alfred::Item<'static>> { vec![] }
+//! use alfred::deadline::Deadline;
+//!
+//! # fn main() {
+//! let query = std::env::args().nth(1).unwrap_or_default();
+//! let (q1, q2) = (query.clone(), query.clone());
+//! let output = Deadline::new(query)
+//!                       .producer("files", move || search_files(&q1))
+//!                       .producer("web", move || search_web(&q2))
+//!                       .run();
+//! if output.is_worker() {
+//!     return;
+//! }
+//! output.write(io::stdout()).unwrap();
+//! # }
+//! ```
+
+use serde_json::{self, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env as std_env;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use aggregate::{panic_message, Failure};
+use env::Environment;
+use runner::error_item;
+use {Item, ItemBuilder};
+
+/// The default time budget.
+pub const DEFAULT_BUDGET: Duration = Duration::from_millis(200);
+
+/// The default time for which finished producers are reused.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// The environment variable that marks the background process.
+const WORKER_VAR: &str = "ALFRED_RS_DEADLINE_WORKER";
+
+type Producer = Box<dyn FnOnce() -> Vec<Item<'static>> + Send>;
+
+/// The items of a finished producer, or the panic message of a failed one.
+type Produced = Result<Vec<Value>, String>;
+
+/// Runs item producers within a time budget.
+///
+/// See the module documentation for details.
+pub struct Deadline {
+    key: String,
+    budget: Duration,
+    max_age: Duration,
+    rerun: f64,
+    loading_title: String,
+    background: bool,
+    env: Environment,
+    cache_dir: Option<PathBuf>,
+    producers: Vec<(String, Producer)>
+}
+
+impl fmt::Debug for Deadline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Deadline")
+         .field("key", &self.key)
+         .field("budget", &self.budget)
+         .field("max_age", &self.max_age)
+         .field("rerun", &self.rerun)
+         .field("loading_title", &self.loading_title)
+         .field("background", &self.background)
+         .field("cache_dir", &self.resolved_cache_dir())
+         .field("producers", &self.producers.iter().map(|p| &p.0).collect::<Vec<_>>())
+         .finish()
+    }
+}
+
+impl Deadline {
+    /// Returns a new `Deadline` that stores finished producers under `key`.
+    ///
+    /// The key identifies the results, so it should include everything the producers depend on,
+    /// which is usually just the query.
+    pub fn new<S: Into<String>>(key: S) -> Deadline {
+        Deadline {
+            key: key.into(),
+            budget: DEFAULT_BUDGET,
+            max_age: DEFAULT_MAX_AGE,
+            rerun: 0.5,
+            loading_title: "Loading…".to_owned(),
+            background: true,
+            env: Environment::from_env(),
+            cache_dir: None,
+            producers: Vec::new()
+        }
+    }
+
+    /// Adds a producer with the given name.
+    ///
+    /// Names identify producers in the cache, so they must be unique. Items are written out in
+    /// the order their producers were added. A producer that panics is shown as an error item.
+    pub fn producer<S, F>(mut self, name: S, producer: F) -> Deadline
+        where S: Into<String>,
+              F: FnOnce() -> Vec<Item<'static>> + Send + 'static
+    {
+        self.producers.push((name.into(), Box::new(producer)));
+        self
+    }
+
+    /// Sets the time to wait for producers. The default is 200 milliseconds.
+    pub fn budget(mut self, budget: Duration) -> Deadline {
+        self.budget = budget;
+        self
+    }
+
+    /// Sets how long the items of finished producers are reused. The default is one minute.
+    pub fn max_age(mut self, max_age: Duration) -> Deadline {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Sets the interval in seconds after which Alfred reruns the script filter while producers
+    /// are unfinished. The default is 0.5.
+    ///
+    /// Alfred accepts intervals between 0.1 and 5 seconds.
+    pub fn rerun(mut self, seconds: f64) -> Deadline {
+        self.rerun = seconds;
+        self
+    }
+
+    /// Sets the title of the item shown while producers are unfinished.
+    pub fn loading_title<S: Into<String>>(mut self, title: S) -> Deadline {
+        self.loading_title = title.into();
+        self
+    }
+
+    /// Sets whether unfinished producers are handed to a background process. The default is
+    /// `true`.
+    ///
+    /// Without a background process, unfinished producers are started again on the next run,
+    /// so producers that never finish within the budget are never shown.
+    pub fn background(mut self, background: bool) -> Deadline {
+        self.background = background;
+        self
+    }
+
+    /// Sets the environment used to find the cache directory.
+    pub fn environment(mut self, env: Environment) -> Deadline {
+        self.env = env;
+        self
+    }
+
+    /// Sets the directory where finished producers are stored.
+    ///
+    /// The default is the `deadline` directory in the workflow cache directory. Without a cache
+    /// directory, nothing is stored and there's no background process.
+    pub fn cache_dir<P: Into<PathBuf>>(mut self, dir: P) -> Deadline {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    fn resolved_cache_dir(&self) -> Option<PathBuf> {
+        self.cache_dir.clone().or_else(|| self.env.workflow_cache().map(|p| p.join("deadline")))
+    }
+
+    /// Runs the producers and returns the output.
+    ///
+    /// Producers stored by an earlier run with the same key aren't run again. The others are run
+    /// in parallel, and their items are included if they finish within the budget.
+    ///
+    /// In the background process, this runs the unfinished producers to completion and returns
+    /// an empty output for which `is_worker()` is `true`.
+    pub fn run(self) -> Output {
+        let cache = self.resolved_cache_dir().map(|dir| {
+            let mut hasher = DefaultHasher::new();
+            self.key.hash(&mut hasher);
+            Cache {
+                path: dir.join(format!("{:016x}.json", hasher.finish())),
+                key: self.key.clone(),
+                max_age: self.max_age
+            }
+        });
+        let mut stored = match cache {
+            Some(ref cache) => cache.load(),
+            None => Stored::new(&self.key)
+        };
+        let max_age = self.max_age;
+        let worker_running = stored.worker.is_some_and(|t| now().saturating_sub(t) < max_age);
+        let names: Vec<String> = self.producers.iter().map(|p| p.0.clone()).collect();
+        let pending: Vec<(String, Producer)> = self.producers.into_iter()
+                                                   .filter(|p| !stored.results.contains_key(&p.0))
+                                                   .collect();
+
+        if let Some(ref cache) = cache {
+            if self.env.var(WORKER_VAR) == Some(&*self.key) {
+                for (name, produced) in spawn(pending) {
+                    cache.update(|s| { s.results.insert(name, produced); });
+                }
+                cache.update(|s| s.worker = None);
+                return Output { items: Vec::new(), rerun: None, worker: true };
+            }
+        }
+
+        if !pending.is_empty() && !worker_running {
+            let count = pending.len();
+            let results = spawn(pending);
+            let deadline = Instant::now() + self.budget;
+            let mut finished = Vec::new();
+            while finished.len() < count {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match results.recv_timeout(timeout) {
+                    Ok(result) => finished.push(result),
+                    Err(_) => break
+                }
+            }
+            let start_worker = finished.len() < count && self.background;
+            if let Some(ref cache) = cache {
+                cache.update(|s| {
+                    s.results.extend(finished.iter().cloned());
+                    if start_worker {
+                        s.worker = Some(now());
+                    }
+                });
+                if start_worker && start_background(&self.key).is_err() {
+                    cache.update(|s| s.worker = None);
+                }
+            }
+            stored.results.extend(finished);
+        }
+
+        let unfinished = names.iter().filter(|n| !stored.results.contains_key(*n)).count();
+        let debug = self.env.is_debug();
+        let mut items = Vec::new();
+        let mut errors = Vec::new();
+        for name in &names {
+            match stored.results.remove(name) {
+                Some(Ok(produced)) => items.extend(produced),
+                Some(Err(message)) => {
+                    let failure = Failure { name: name.clone(), error: message.into() };
+                    errors.push(error_item(&failure, debug).to_json());
+                }
+                None => {}
+            }
+        }
+        items.extend(errors);
+        let mut rerun = None;
+        if unfinished > 0 {
+            items.push(ItemBuilder::new(self.loading_title)
+                                   .subtitle(format!("{} of {} sources still loading", unfinished,
+                                                     names.len()))
+                                   .valid(false)
+                                   .into_item()
+                                   .to_json());
+            rerun = Some(self.rerun);
+        }
+        Output { items, rerun, worker: false }
+    }
+}
+
+/// The output of `Deadline::run()`.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Output {
+    items: Vec<Value>,
+    rerun: Option<f64>,
+    worker: bool
+}
+
+impl Output {
+    /// Returns whether all producers finished.
+    pub fn is_complete(&self) -> bool {
+        self.rerun.is_none()
+    }
+
+    /// Returns whether this is the background process, in which case the program should exit
+    /// without writing anything.
+    ///
+    /// See the module documentation for details.
+    pub fn is_worker(&self) -> bool {
+        self.worker
+    }
+
+    /// Writes a complete JSON document representing the output to the `Write`.
+    ///
+    /// The `Write` is flushed after the JSON document is written. Nothing is written in the
+    /// background process.
+    pub fn write<W: Write>(self, mut w: W) -> io::Result<()> {
+        if self.worker {
+            return Ok(());
+        }
+        write!(&mut w, "{}", self.into_json())?;
+        w.flush()
+    }
+
+    /// Serializes the output into its JSON representation.
+    pub fn into_json(self) -> Value {
+        let mut root = serde_json::Map::new();
+        root.insert("items".to_owned(), Value::Array(self.items));
+        if let Some(rerun) = self.rerun {
+            root.insert("rerun".to_owned(), json!(rerun));
+        }
+        Value::Object(root)
+    }
+}
+
+/// Runs each producer on its own thread, returning a channel that receives the name and result
+/// of each producer as it finishes.
+fn spawn(producers: Vec<(String, Producer)>) -> mpsc::Receiver<(String, Produced)> {
+    let (tx, rx) = mpsc::channel();
+    for (name, producer) in producers {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let produced = match panic::catch_unwind(AssertUnwindSafe(producer)) {
+                Ok(items) => Ok(items.iter().map(Item::to_json).collect()),
+                Err(panic) => Err(panic_message(&*panic))
+            };
+            let _ = tx.send((name, produced));
+        });
+    }
+    rx
+}
+
+/// Starts the running executable again as the background process for `key`.
+fn start_background(key: &str) -> io::Result<()> {
+    Command::new(std_env::current_exe()?)
+            .args(std_env::args_os().skip(1))
+            .env(WORKER_VAR, key)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// The stored state for a key.
+struct Stored {
+    key: String,
+    created: Duration,
+    results: HashMap<String, Produced>,
+    worker: Option<Duration>
+}
+
+impl Stored {
+    fn new(key: &str) -> Stored {
+        Stored { key: key.to_owned(), created: now(), results: HashMap::new(), worker: None }
+    }
+
+    fn from_json(value: &Value) -> Option<Stored> {
+        let mut results: HashMap<String, Produced> = value["results"].as_object()?.iter()
+            .filter_map(|(name, items)| Some((name.clone(), Ok(items.as_array()?.clone()))))
+            .collect();
+        if let Some(failures) = value["failures"].as_object() {
+            results.extend(failures.iter().filter_map(|(name, message)| {
+                Some((name.clone(), Err(message.as_str()?.to_owned())))
+            }));
+        }
+        Some(Stored {
+            key: value["key"].as_str()?.to_owned(),
+            created: Duration::from_millis(value["created"].as_u64()?),
+            results,
+            worker: value["worker"].as_u64().map(Duration::from_millis)
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let mut results = serde_json::Map::new();
+        let mut failures = serde_json::Map::new();
+        for (name, produced) in &self.results {
+            match *produced {
+                Ok(ref items) => results.insert(name.clone(), json!(items)),
+                Err(ref message) => failures.insert(name.clone(), json!(message))
+            };
+        }
+        json!({
+            "key": self.key,
+            "created": self.created.as_millis() as u64,
+            "results": results,
+            "failures": failures,
+            "worker": self.worker.map(|t| t.as_millis() as u64)
+        })
+    }
+}
+
+/// The file that stores the state for a key.
+///
+/// Errors are ignored, because a missing cache only costs time.
+struct Cache {
+    path: PathBuf,
+    key: String,
+    max_age: Duration
+}
+
+impl Cache {
+    /// Returns the stored state, or an empty state if it's missing or too old.
+    fn load(&self) -> Stored {
+        fs::read(&self.path).ok()
+                            .and_then(|data| serde_json::from_slice(&data).ok())
+                            .and_then(|value| Stored::from_json(&value))
+                            .filter(|s| s.key == self.key)
+                            .filter(|s| now().saturating_sub(s.created) < self.max_age)
+                            .unwrap_or_else(|| Stored::new(&self.key))
+    }
+
+    /// Reads the stored state, applies `f`, and writes it back.
+    fn update<F: FnOnce(&mut Stored)>(&self, f: F) {
+        let mut stored = self.load();
+        f(&mut stored);
+        let tmp = self.path.with_extension(format!("{}.tmp", process::id()));
+        let result = self.path.parent().map_or(Ok(()), fs::create_dir_all)
+                         .and_then(|_| fs::write(&tmp, stored.to_json().to_string()))
+                         .and_then(|_| fs::rename(&tmp, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+}
+
+#[test]
+fn test_deadline() {
+    let dir = ::std::env::temp_dir().join(format!("alfred-deadline-test-{}", process::id()));
+    let env = Environment::from_map(vec![("alfred_workflow_cache", dir.to_str().unwrap())]);
+    let deadline = || Deadline::new("query").environment(env.clone()).background(false);
+
+    let output = deadline().budget(Duration::from_millis(50))
+                           .producer("fast", || vec![Item::new("Fast")])
+                           .producer("slow", || {
+                               thread::sleep(Duration::from_secs(1));
+                               vec![Item::new("Slow")]
+                           })
+                           .run();
+    assert!(!output.is_complete());
+    let json = output.into_json();
+    assert_eq!(json["items"][0], json!({"title": "Fast"}));
+    assert_eq!(json["items"][1]["title"], "Loading…");
+    assert_eq!(json["items"][1]["subtitle"], "1 of 2 sources still loading");
+    assert_eq!(json["rerun"], 0.5);
+
+    // The finished producer is reused, and the output is complete once the other one finishes.
+    let output = deadline().producer("fast", || panic!("fast ran again"))
+                           .producer("slow", || vec![Item::new("Slow")])
+                           .run();
+    assert!(output.is_complete());
+    assert_eq!(output.into_json(), json!({"items": [{"title": "Fast"}, {"title": "Slow"}]}));
+
+    // Other keys and expired results aren't reused.
+    for deadline in [Deadline::new("other").environment(env.clone()).background(false),
+                     deadline().max_age(Duration::from_secs(0))] {
+        let output = deadline.producer("fast", || vec![Item::new("Again")]).run();
+        assert_eq!(output.into_json(), json!({"items": [{"title": "Again"}]}));
+    }
+
+    // A panicking producer becomes an error item after the results, and isn't run again.
+    let deadline = || Deadline::new("panic").environment(env.clone()).background(false);
+    let output = deadline().producer("broken", || panic!("oops"))
+                           .producer("fast", || vec![Item::new("Fast")])
+                           .run();
+    assert!(output.is_complete());
+    let json = output.into_json();
+    assert_eq!(json["items"][0], json!({"title": "Fast"}));
+    assert_eq!(json["items"][1]["title"], "Error: broken failed");
+    assert_eq!(json["items"][1]["subtitle"], "panicked: oops");
+    let output = deadline().producer("broken", || vec![Item::new("Fixed")])
+                           .producer("fast", || vec![Item::new("Fast")])
+                           .run();
+    assert_eq!(output.into_json(), json);
+
+    // The background process stores the unfinished producers and returns without output.
+    let worker_env = Environment::from_map(vec![("alfred_workflow_cache", dir.to_str().unwrap()),
+                                                (WORKER_VAR, "worker")]);
+    let output = Deadline::new("worker").environment(worker_env)
+                                        .producer("slow", || vec![Item::new("Slow")])
+                                        .run();
+    assert!(output.is_worker());
+    let mut written = Vec::new();
+    output.write(&mut written).unwrap();
+    assert!(written.is_empty());
+    let output = Deadline::new("worker").environment(env.clone())
+                                        .producer("slow", || panic!("slow ran again"))
+                                        .run();
+    assert!(!output.is_worker());
+    assert_eq!(output.into_json(), json!({"items": [{"title": "Slow"}]}));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_deadline_with_crash_hook() {
+    use crash;
+    use runner::Format;
+
+    let name = "deadline::test_deadline_with_crash_hook";
+    let output = crash::run_with_hook(name, Format::Json, || {
+        Deadline::new("query").environment(Environment::new())
+                              .producer("broken", || panic!("oops"))
+                              .producer("fast", || vec![Item::new("Fast")])
+                              .run()
+                              .write(crash::stdout())
+                              .unwrap();
+    });
+    if let Some(output) = output {
+        let document = crash::json_document(&output);
+        assert_eq!(document["items"][0]["title"], "Fast");
+        assert_eq!(document["items"][1]["title"], "Error: broken failed");
+    }
+}
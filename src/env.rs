@@ -2,52 +2,374 @@
 //!
 //! See https://www.alfredapp.com/help/workflows/script-environment-variables/
 //! for more info.
+//!
+//! The free functions in this module read the process environment each time they're called. Code
+//! that wants to be testable without mutating the process environment can instead take an
+//! `Environment` snapshot, which can be captured from the process with `Environment::from_env()`
+//! or built from any map of variables with `Environment::from_map()`.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! use alfred::env::{Environment, Subtext};
+//!
+//! # fn main() {
+//! let env = Environment::from_map(vec![
+//!     ("alfred_debug", "1"),
+//!     ("alfred_theme_subtext", "2"),
+//!     ("alfred_workflow_cache", "/tmp/cache"),
+//! ]);
+//! assert!(env.is_debug());
+//! assert_eq!(env.theme_subtext(), Some(Subtext::SelectedResult));
+//! assert_eq!(env.workflow_cache(), Some("/tmp/cache".into()));
+//! # }
+//! ```
 
+use std::collections::{hash_map, HashMap};
 use std::env;
+use std::iter::FromIterator;
 use std::path::PathBuf;
 
+use color::Color;
+
+// The variables Alfred sets, shared by the `Environment` methods and the free functions.
+const PREFERENCES: &str = "alfred_preferences";
+const PREFERENCES_LOCALHASH: &str = "alfred_preferences_localhash";
+const THEME: &str = "alfred_theme";
+const THEME_BACKGROUND: &str = "alfred_theme_background";
+const THEME_SELECTION_BACKGROUND: &str = "alfred_theme_selection_background";
+const THEME_SUBTEXT: &str = "alfred_theme_subtext";
+const VERSION: &str = "alfred_version";
+const VERSION_BUILD: &str = "alfred_version_build";
+const WORKFLOW_BUNDLEID: &str = "alfred_workflow_bundleid";
+const WORKFLOW_CACHE: &str = "alfred_workflow_cache";
+const WORKFLOW_DATA: &str = "alfred_workflow_data";
+const WORKFLOW_NAME: &str = "alfred_workflow_name";
+const WORKFLOW_UID: &str = "alfred_workflow_uid";
+const WORKFLOW_VERSION: &str = "alfred_workflow_version";
+const DEBUG: &str = "alfred_debug";
+
+/// A snapshot of the environment variables visible to a workflow.
+///
+/// The accessor methods mirror the free functions in this module, but read from the snapshot
+/// instead of the process environment.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Environment {
+    vars: HashMap<String, String>
+}
+
+impl Environment {
+    /// Returns a new empty `Environment`.
+    pub fn new() -> Environment {
+        Default::default()
+    }
+
+    /// Returns a snapshot of the current process environment.
+    ///
+    /// Variables whose name or value is not valid unicode are skipped, matching the behavior of
+    /// `std::env::var()`.
+    pub fn from_env() -> Environment {
+        Environment {
+            vars: env::vars_os().filter_map(|(k, v)| {
+                match (k.into_string(), v.into_string()) {
+                    (Ok(k), Ok(v)) => Some((k, v)),
+                    _ => None
+                }
+            }).collect()
+        }
+    }
+
+    /// Returns an `Environment` containing the given variables.
+    pub fn from_map<I,K,V>(vars: I) -> Environment
+        where I: IntoIterator<Item=(K,V)>,
+              K: Into<String>,
+              V: Into<String>
+    {
+        Environment {
+            vars: vars.into_iter().map(|(k,v)| (k.into(), v.into())).collect()
+        }
+    }
+
+    /// Returns the value of the variable `key`, if present.
+    pub fn var(&self, key: &str) -> Option<&str> {
+        self.vars.get(key).map(|s| s.as_str())
+    }
+
+    /// Sets the variable `key` to `value`.
+    pub fn set_var<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.vars.insert(key.into(), value.into());
+    }
+
+    /// Removes the variable `key`.
+    pub fn remove_var(&mut self, key: &str) {
+        self.vars.remove(key);
+    }
+
+    /// Returns an iterator over all variables in the snapshot.
+    pub fn vars(&self) -> Vars<'_> {
+        Vars { iter: self.vars.iter() }
+    }
+
+    /// Returns an iterator over the variables that aren't Alfred's own `alfred_*` variables.
+    ///
+    /// This includes the workflow's user-configurable variables, along with any other variables
+    /// inherited from the process environment.
+    pub fn user_vars(&self) -> UserVars<'_> {
+        UserVars { iter: self.vars() }
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.var(key).map(String::from)
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.var(key).map(PathBuf::from)
+    }
+
+    /// Returns the location of the Alfred.alfredpreferences.
+    ///
+    /// See `alfred::env::preferences()`.
+    pub fn preferences(&self) -> Option<PathBuf> {
+        self.path(PREFERENCES)
+    }
+
+    /// Returns the location of local (Mac-specific) preferences.
+    ///
+    /// See `alfred::env::local_preferences()`.
+    pub fn local_preferences(&self) -> Option<PathBuf> {
+        match (self.preferences(), self.var(PREFERENCES_LOCALHASH)) {
+            (Some(mut prefs), Some(hash)) => {
+                prefs.extend(["preferences","local",hash].iter());
+                Some(prefs)
+            }
+            _ => None
+        }
+    }
+
+    /// Returns the current Alfred theme.
+    ///
+    /// See `alfred::env::theme()`.
+    pub fn theme(&self) -> Option<String> {
+        self.string(THEME)
+    }
+
+    /// Returns the color of the theme background.
+    ///
+    /// See `alfred::env::theme_background_str()`.
+    pub fn theme_background_str(&self) -> Option<String> {
+        self.string(THEME_BACKGROUND)
+    }
+
+    /// Returns the color of the theme's selected item background.
+    ///
+    /// See `alfred::env::theme_selection_background_str()`.
+    pub fn theme_selection_background_str(&self) -> Option<String> {
+        self.string(THEME_SELECTION_BACKGROUND)
+    }
+
+    /// Returns the parsed color of the theme background.
+    ///
+    /// See `alfred::env::theme_background()`.
+    pub fn theme_background(&self) -> Option<Color> {
+        self.var(THEME_BACKGROUND).and_then(|s| s.parse().ok())
+    }
+
+    /// Returns the parsed color of the theme's selected item background.
+    ///
+    /// See `alfred::env::theme_selection_background()`.
+    pub fn theme_selection_background(&self) -> Option<Color> {
+        self.var(THEME_SELECTION_BACKGROUND).and_then(|s| s.parse().ok())
+    }
+
+    /// Returns the subtext mode the user has selected in the Appearance preferences.
+    ///
+    /// See `alfred::env::theme_subtext()`.
+    pub fn theme_subtext(&self) -> Option<Subtext> {
+        match self.var(THEME_SUBTEXT) {
+            Some("0") => Some(Subtext::Always),
+            Some("1") => Some(Subtext::AlternativeActions),
+            Some("2") => Some(Subtext::SelectedResult),
+            Some("3") => Some(Subtext::Never),
+            _ => None
+        }
+    }
+
+    /// Returns the version of Alfred.
+    ///
+    /// See `alfred::env::version()`.
+    pub fn version(&self) -> Option<String> {
+        self.string(VERSION)
+    }
+
+    /// Returns the build of Alfred.
+    ///
+    /// See `alfred::env::version_build()`.
+    pub fn version_build(&self) -> Option<i32> {
+        self.var(VERSION_BUILD).and_then(|s| s.parse().ok())
+    }
+
+    /// Returns the bundle ID of the current running workflow.
+    ///
+    /// See `alfred::env::workflow_bundle_id()`.
+    pub fn workflow_bundle_id(&self) -> Option<String> {
+        self.string(WORKFLOW_BUNDLEID)
+    }
+
+    /// Returns the recommended location for volatile workflow data.
+    ///
+    /// See `alfred::env::workflow_cache()`.
+    pub fn workflow_cache(&self) -> Option<PathBuf> {
+        self.path(WORKFLOW_CACHE)
+    }
+
+    /// Returns the recommended location for non-volatile workflow data.
+    ///
+    /// See `alfred::env::workflow_data()`.
+    pub fn workflow_data(&self) -> Option<PathBuf> {
+        self.path(WORKFLOW_DATA)
+    }
+
+    /// Returns the name of the currently running workflow.
+    ///
+    /// See `alfred::env::workflow_name()`.
+    pub fn workflow_name(&self) -> Option<String> {
+        self.string(WORKFLOW_NAME)
+    }
+
+    /// Returns the unique ID of the currently running workflow.
+    ///
+    /// See `alfred::env::workflow_uid()`.
+    pub fn workflow_uid(&self) -> Option<String> {
+        self.string(WORKFLOW_UID)
+    }
+
+    /// Returns the version of the currently running workflow.
+    ///
+    /// See `alfred::env::workflow_version()`.
+    pub fn workflow_version(&self) -> Option<String> {
+        self.string(WORKFLOW_VERSION)
+    }
+
+    /// Returns `true` if the user has the debug panel open for the workflow.
+    ///
+    /// See `alfred::env::is_debug()`.
+    pub fn is_debug(&self) -> bool {
+        self.var(DEBUG) == Some("1")
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K,V)> for Environment {
+    fn from_iter<I: IntoIterator<Item=(K,V)>>(iter: I) -> Environment {
+        Environment::from_map(iter)
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K,V)> for Environment {
+    fn extend<I: IntoIterator<Item=(K,V)>>(&mut self, iter: I) {
+        self.vars.extend(iter.into_iter().map(|(k,v)| (k.into(), v.into())));
+    }
+}
+
+/// An iterator over the variables of an `Environment`.
+///
+/// This is returned by `Environment::vars()`.
+#[derive(Clone,Debug)]
+pub struct Vars<'a> {
+    iter: hash_map::Iter<'a, String, String>
+}
+
+impl<'a> Iterator for Vars<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        self.iter.next().map(|(k,v)| (k.as_str(), v.as_str()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the non-Alfred variables of an `Environment`.
+///
+/// This is returned by `Environment::user_vars()`.
+#[derive(Clone,Debug)]
+pub struct UserVars<'a> {
+    iter: Vars<'a>
+}
+
+impl<'a> Iterator for UserVars<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        self.iter.by_ref().find(|&(k, _)| !k.starts_with("alfred_"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Returns an `Environment` containing only the given variables from the process environment.
+///
+/// The free functions use this rather than `Environment::from_env()`, so they don't copy the whole
+/// environment to read one or two variables.
+fn process_vars(keys: &[&str]) -> Environment {
+    Environment::from_map(keys.iter().filter_map(|&key| env::var(key).ok().map(|v| (key, v))))
+}
+
 /// Returns the location of the Alfred.alfredpreferences.
 ///
 /// Example output: `"/Users/Crayons/Dropbox/Alfred/Alfred.alfredpreferences"`
 pub fn preferences() -> Option<PathBuf> {
-    env::var("alfred_preferences").ok().map(PathBuf::from)
+    process_vars(&[PREFERENCES]).preferences()
 }
 
 /// Returns the location of local (Mac-specific) preferences.
 ///
 /// Example output: `"/Users/Crayons/Dropbox/Alfred/Alfred.alfredpreferences/preferences/local/adbd4f66bc3ae8493832af61a41ee609b20d8705"`
 pub fn local_preferences() -> Option<PathBuf> {
-    match (preferences(),env::var("alfred_preferences_localhash")) {
-        (Some(mut prefs),Ok(hash)) => {
-            prefs.extend(["preferences","local",&hash].iter());
-            Some(prefs)
-        }
-        _ => None
-    }
+    process_vars(&[PREFERENCES, PREFERENCES_LOCALHASH]).local_preferences()
 }
 
 /// Returns the current Alfred theme.
 ///
 /// Example output: `"alfred.theme.yosemite"`
 pub fn theme() -> Option<String> {
-    env::var("alfred_theme").ok()
+    process_vars(&[THEME]).theme()
 }
 
 /// Returns the color of the theme background.
 ///
 /// Example output: `"rgba(255,255,255,0.98)"`
-// TODO: can we parse this?
-// Is this always in rgba(), or can it be any web color?
+///
+/// See `theme_background()` for the parsed color.
 pub fn theme_background_str() -> Option<String> {
-    env::var("alfred_theme_background").ok()
+    process_vars(&[THEME_BACKGROUND]).theme_background_str()
+}
+
+/// Returns the color of the theme background.
+///
+/// Returns `None` if the variable is missing or can't be parsed as a color.
+pub fn theme_background() -> Option<Color> {
+    process_vars(&[THEME_BACKGROUND]).theme_background()
 }
 
 /// Returns the color of the theme's selected item background.
 ///
 /// Example output: `"rgba(255,255,255,0.98)"`
-// TODO: see `theme_background_str()`
+///
+/// See `theme_selection_background()` for the parsed color.
 pub fn theme_selection_background_str() -> Option<String> {
-    env::var("alfred_theme_selection_background").ok()
+    process_vars(&[THEME_SELECTION_BACKGROUND]).theme_selection_background_str()
+}
+
+/// Returns the color of the theme's selected item background.
+///
+/// Returns `None` if the variable is missing or can't be parsed as a color.
+pub fn theme_selection_background() -> Option<Color> {
+    process_vars(&[THEME_SELECTION_BACKGROUND]).theme_selection_background()
 }
 
 /// The subtext mode in the Appearance preferences.
@@ -65,34 +387,28 @@ pub enum Subtext {
 
 /// Returns the subtext mode the user has selected in the Appearance preferences.
 pub fn theme_subtext() -> Option<Subtext> {
-    match env::var("alfred_theme_subtext").as_ref().map(|s| s.as_ref()) {
-        Ok("0") => Some(Subtext::Always),
-        Ok("1") => Some(Subtext::AlternativeActions),
-        Ok("2") => Some(Subtext::SelectedResult),
-        Ok("3") => Some(Subtext::Never),
-        _ => None
-    }
+    process_vars(&[THEME_SUBTEXT]).theme_subtext()
 }
 
 /// Returns the version of Alfred.
 ///
 /// Example output: `"3.2.1"`
 pub fn version() -> Option<String> {
-    env::var("alfred_version").ok()
+    process_vars(&[VERSION]).version()
 }
 
 /// Returns the build of Alfred.
 ///
 /// Example output: `768`
 pub fn version_build() -> Option<i32> {
-    env::var("alfred_version_build").ok().and_then(|s| s.parse().ok())
+    process_vars(&[VERSION_BUILD]).version_build()
 }
 
 /// Returns the bundle ID of the current running workflow.
 ///
 /// Example output: `"com.alfredapp.david.googlesuggest"`
 pub fn workflow_bundle_id() -> Option<String> {
-    env::var("alfred_workflow_bundleid").ok()
+    process_vars(&[WORKFLOW_BUNDLEID]).workflow_bundle_id()
 }
 
 /// Returns the recommended location for volatile workflow data.
@@ -100,7 +416,7 @@ pub fn workflow_bundle_id() -> Option<String> {
 ///
 /// Example output: `"/Users/Crayons/Library/Caches/com.runningwithcrayons.Alfred-2/Workflow Data/com.alfredapp.david.googlesuggest"`
 pub fn workflow_cache() -> Option<PathBuf> {
-    env::var("alfred_workflow_cache").ok().map(PathBuf::from)
+    process_vars(&[WORKFLOW_CACHE]).workflow_cache()
 }
 
 /// Returns the recommended location for non-volatile workflow data.
@@ -108,32 +424,54 @@ pub fn workflow_cache() -> Option<PathBuf> {
 ///
 /// Example output: `"/Users/Crayons/Library/Application Support/Alfred 2/Workflow Data/com.alfredapp.david.googlesuggest"`
 pub fn workflow_data() -> Option<PathBuf> {
-    env::var("alfred_workflow_data").ok().map(PathBuf::from)
+    process_vars(&[WORKFLOW_DATA]).workflow_data()
 }
 
 /// Returns the name of the currently running workflow.
 ///
 /// Example output: `"Google Suggest"`
 pub fn workflow_name() -> Option<String> {
-    env::var("alfred_workflow_name").ok()
+    process_vars(&[WORKFLOW_NAME]).workflow_name()
 }
 
 /// Returns the unique ID of the currently running workflow.
 ///
 /// Example output: `"user.workflow.B0AC54EC-601C-479A-9428-01F9FD732959"`
 pub fn workflow_uid() -> Option<String> {
-    env::var("alfred_workflow_uid").ok()
+    process_vars(&[WORKFLOW_UID]).workflow_uid()
 }
 
 /// Returns the version of the currently running workflow.
 pub fn workflow_version() -> Option<String> {
-    env::var("alfred_workflow_version").ok()
+    process_vars(&[WORKFLOW_VERSION]).workflow_version()
 }
 
 /// Returns `true` if the user has the debug panel open for the workflow.
 pub fn is_debug() -> bool {
-    match env::var("alfred_debug") {
-        Ok(val) => val == "1",
-        _ => false
-    }
+    process_vars(&[DEBUG]).is_debug()
+}
+
+#[test]
+fn test_environment() {
+    let mut env = Environment::from_map(vec![
+        ("alfred_preferences", "/Users/Crayons/Alfred.alfredpreferences"),
+        ("alfred_preferences_localhash", "adbd4f66"),
+        ("alfred_theme_subtext", "3"),
+        ("alfred_version_build", "768"),
+        ("alfred_debug", "0"),
+        ("alfred_theme_background", "rgba(40,40,40,0.95)"),
+        ("alfred_theme_selection_background", "bogus"),
+    ]);
+    assert_eq!(env.local_preferences(),
+               Some(PathBuf::from("/Users/Crayons/Alfred.alfredpreferences/preferences/local/adbd4f66")));
+    assert_eq!(env.theme_subtext(), Some(Subtext::Never));
+    assert_eq!(env.version_build(), Some(768));
+    assert_eq!(env.workflow_name(), None);
+    assert_eq!(env.theme_background(), Some(Color::new(40, 40, 40, 0.95)));
+    assert_eq!(env.theme_selection_background(), None);
+    assert!(!env.is_debug());
+    env.set_var("alfred_debug", "1");
+    assert!(env.is_debug());
+    env.remove_var("alfred_preferences_localhash");
+    assert_eq!(env.local_preferences(), None);
 }
@@ -0,0 +1,182 @@
+//! Escaping text the way Alfred's Run Script object escapes `{query}`
+//!
+//! A Run Script object that substitutes `{query}` into its script, rather than passing the input
+//! as an argument, first escapes the input according to the checkboxes in its "Escaping" menu.
+//! Each checkbox puts a backslash before the characters it covers:
+//!
+//! | Flag            | Characters              |
+//! |-----------------|-------------------------|
+//! | `SPACES`        | space                   |
+//! | `BACKQUOTES`    | `` ` ``                 |
+//! | `DOUBLE_QUOTES` | `"`                     |
+//! | `BRACKETS`      | `(` `)` `[` `]` `{` `}` |
+//! | `SEMICOLONS`    | `;`                     |
+//! | `DOLLARS`       | `$`                     |
+//! | `BACKSLASHES`   | `\`                     |
+//!
+//! `escape()` reproduces this, and `unescape()` reverses it. Unescaping only removes backslashes
+//! before characters covered by the flags, so `unescape(&escape(s, e), e)` is always `s`.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::escaping::{escape, unescape, Escaping};
+//!
+//! let escaped = escape(r#"say "hi" for $5"#, Escaping::default());
+//! assert_eq!(escaped, r#"say \"hi\" for \$5"#);
+//! assert_eq!(unescape(&escaped, Escaping::default()), r#"say "hi" for $5"#);
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::ops::BitOr;
+
+/// The characters that Alfred escapes in `{query}` before substituting it into a script.
+///
+/// Flags are combined with `|`. The default is the set Alfred picks for bash scripts: backquotes,
+/// double quotes, dollars, and backslashes.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Escaping(u32);
+
+impl Escaping {
+    /// Escape nothing.
+    pub const NONE: Escaping = Escaping(0);
+    /// Escape spaces.
+    pub const SPACES: Escaping = Escaping(1);
+    /// Escape backquotes.
+    pub const BACKQUOTES: Escaping = Escaping(2);
+    /// Escape double quotes.
+    pub const DOUBLE_QUOTES: Escaping = Escaping(4);
+    /// Escape round, square, and curly brackets.
+    pub const BRACKETS: Escaping = Escaping(8);
+    /// Escape semicolons.
+    pub const SEMICOLONS: Escaping = Escaping(16);
+    /// Escape dollars.
+    pub const DOLLARS: Escaping = Escaping(32);
+    /// Escape backslashes.
+    pub const BACKSLASHES: Escaping = Escaping(64);
+    /// Escape everything.
+    pub const ALL: Escaping = Escaping(127);
+
+    /// Returns the flags as stored in `info.plist`.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the flags for the value stored in `info.plist`, ignoring unknown bits.
+    pub fn from_bits_truncate(bits: u32) -> Escaping {
+        Escaping(bits & Escaping::ALL.0)
+    }
+
+    /// Returns whether all the flags in `other` are set.
+    pub fn contains(self, other: Escaping) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether `c` is escaped.
+    pub fn escapes(self, c: char) -> bool {
+        let flag = match c {
+            ' ' => Escaping::SPACES,
+            '`' => Escaping::BACKQUOTES,
+            '"' => Escaping::DOUBLE_QUOTES,
+            '(' | ')' | '[' | ']' | '{' | '}' => Escaping::BRACKETS,
+            ';' => Escaping::SEMICOLONS,
+            '$' => Escaping::DOLLARS,
+            '\\' => Escaping::BACKSLASHES,
+            _ => return false
+        };
+        self.contains(flag)
+    }
+}
+
+impl Default for Escaping {
+    fn default() -> Escaping {
+        Escaping::BACKQUOTES | Escaping::DOUBLE_QUOTES | Escaping::DOLLARS | Escaping::BACKSLASHES
+    }
+}
+
+impl BitOr for Escaping {
+    type Output = Escaping;
+
+    fn bitor(self, rhs: Escaping) -> Escaping {
+        Escaping(self.0 | rhs.0)
+    }
+}
+
+/// Escapes `s` the way Alfred does with the given flags.
+pub fn escape(s: &str, escaping: Escaping) -> Cow<'_, str> {
+    if !s.chars().any(|c| escaping.escapes(c)) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        if escaping.escapes(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// Reverses `escape()` with the same flags.
+///
+/// A backslash is removed only if it's followed by a character covered by the flags. Other
+/// backslashes are kept.
+pub fn unescape(s: &str, escaping: Escaping) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if escaping.escapes(next) {
+                    out.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+#[test]
+fn test_escape() {
+    let cases: &[(&str, Escaping, &str)] = &[
+        ("plain", Escaping::ALL, "plain"),
+        ("a b", Escaping::SPACES, r"a\ b"),
+        ("a b", Escaping::NONE, "a b"),
+        ("`ls`", Escaping::BACKQUOTES, r"\`ls\`"),
+        (r#""hi""#, Escaping::DOUBLE_QUOTES, r#"\"hi\""#),
+        ("(a)[b]{c}", Escaping::BRACKETS, r"\(a\)\[b\]\{c\}"),
+        ("a;b", Escaping::SEMICOLONS, r"a\;b"),
+        ("$HOME", Escaping::DOLLARS, r"\$HOME"),
+        (r"C:\dir", Escaping::BACKSLASHES, r"C:\\dir"),
+        (r#"say "hi" for $5 \o/"#, Escaping::default(), r#"say \"hi\" for \$5 \\o/"#),
+        (r#"`$(x;y)` "z" \"#, Escaping::ALL, r#"\`\$\(x\;y\)\`\ \"z\"\ \\"#),
+        (r#"\" \ "#, Escaping::DOUBLE_QUOTES, r#"\\" \ "#),
+        ("ünï côdé", Escaping::SPACES, r"ünï\ côdé"),
+    ];
+    for &(input, escaping, escaped) in cases {
+        assert_eq!(escape(input, escaping), escaped, "escape({:?}, {:?})", input, escaping);
+        assert_eq!(unescape(escaped, escaping), input, "unescape({:?}, {:?})", escaped, escaping);
+    }
+
+    let cases: &[(&str, Escaping, &str)] = &[
+        (r"a\b", Escaping::ALL, r"a\b"),
+        (r"trailing\", Escaping::ALL, r"trailing\"),
+        (r"\ ", Escaping::NONE, r"\ "),
+        (r"\\\ ", Escaping::SPACES, r"\\ "),
+    ];
+    for &(input, escaping, unescaped) in cases {
+        assert_eq!(unescape(input, escaping), unescaped, "unescape({:?}, {:?})", input, escaping);
+    }
+
+    assert_eq!(Escaping::default().bits(), 102);
+    assert_eq!(Escaping::from_bits_truncate(0xff), Escaping::ALL);
+}
@@ -0,0 +1,180 @@
+//! Selection of light or dark icon variants based on the active Alfred theme
+//!
+//! Icons that are drawn in a single color tend to disappear against a theme background of the
+//! same color. Workflows that ship a white and a black variant of each icon can use an
+//! `IconResolver` to pick the variant that contrasts with the user's theme.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! use alfred::icon::{Appearance, IconResolver};
+//!
+//! # fn main() {
+//! // Normally this would be `IconResolver::from_env()`.
+//! let resolver = IconResolver::new(Appearance::Dark).directory("icons");
+//!
+//! let item = alfred::ItemBuilder::new("Settings")
+//!                                .icon(resolver.icon("gear"))
+//!                                .into_item();
+//! assert_eq!(item.icon, Some(alfred::Icon::Path("icons/gear-white.png".into())));
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use color::Color;
+use env::Environment;
+use Icon;
+
+/// Whether the theme background is light or dark.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Appearance {
+    /// The theme has a light background, so icons should be dark.
+    Light,
+    /// The theme has a dark background, so icons should be light.
+    Dark
+}
+
+impl Appearance {
+    /// Classifies a background color as light or dark.
+    ///
+    /// Translucent colors are composited over a neutral gray first, as Alfred's window is drawn on
+    /// top of whatever is behind it and a mostly-transparent background doesn't look like its own
+    /// color.
+    pub fn from_color(color: &Color) -> Appearance {
+        if color.over(&Color::rgb(128, 128, 128)).is_dark() {
+            Appearance::Dark
+        } else {
+            Appearance::Light
+        }
+    }
+
+    /// Returns the appearance of the theme background in `env`, if the background color is set
+    /// and can be parsed.
+    pub fn from_environment(env: &Environment) -> Option<Appearance> {
+        env.theme_background().map(|c| Appearance::from_color(&c))
+    }
+
+    /// Returns the appearance of the theme background, if the background color is set and can be
+    /// parsed.
+    pub fn from_env() -> Option<Appearance> {
+        Appearance::from_environment(&Environment::from_env())
+    }
+}
+
+/// Maps logical icon names to the icon file that suits the active theme.
+///
+/// Icon paths are built as `<directory>/<name><suffix>.<extension>`, where the suffix is
+/// `"-white"` on dark themes and `"-black"` on light themes by default. The directory is relative
+/// to the workflow directory and defaults to the workflow directory itself.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct IconResolver {
+    appearance: Appearance,
+    directory: String,
+    extension: String,
+    dark_suffix: String,
+    light_suffix: String
+}
+
+impl IconResolver {
+    /// Returns a new `IconResolver` for the given appearance.
+    pub fn new(appearance: Appearance) -> IconResolver {
+        IconResolver {
+            appearance,
+            directory: String::new(),
+            extension: "png".to_owned(),
+            dark_suffix: "-white".to_owned(),
+            light_suffix: "-black".to_owned()
+        }
+    }
+
+    /// Returns a new `IconResolver` for the theme described by `env`.
+    ///
+    /// If `alfred_theme_background` is missing or can't be parsed, `Appearance::Light` is used,
+    /// as that matches Alfred's default theme.
+    pub fn from_environment(env: &Environment) -> IconResolver {
+        IconResolver::new(Appearance::from_environment(env).unwrap_or(Appearance::Light))
+    }
+
+    /// Returns a new `IconResolver` for the active theme.
+    ///
+    /// See `from_environment()` for the fallback behavior.
+    pub fn from_env() -> IconResolver {
+        IconResolver::from_environment(&Environment::from_env())
+    }
+
+    /// Returns the appearance icons are resolved for.
+    pub fn appearance(&self) -> Appearance {
+        self.appearance
+    }
+
+    /// Sets the directory, relative to the workflow directory, that contains the icons.
+    pub fn directory<S: Into<String>>(mut self, directory: S) -> IconResolver {
+        self.directory = directory.into();
+        self
+    }
+
+    /// Sets the file extension of the icons. The default is `"png"`.
+    pub fn extension<S: Into<String>>(mut self, extension: S) -> IconResolver {
+        self.extension = extension.into();
+        self
+    }
+
+    /// Sets the suffix of the icon variants used on dark themes. The default is `"-white"`.
+    pub fn dark_suffix<S: Into<String>>(mut self, suffix: S) -> IconResolver {
+        self.dark_suffix = suffix.into();
+        self
+    }
+
+    /// Sets the suffix of the icon variants used on light themes. The default is `"-black"`.
+    pub fn light_suffix<S: Into<String>>(mut self, suffix: S) -> IconResolver {
+        self.light_suffix = suffix.into();
+        self
+    }
+
+    /// Returns the path of the icon variant for `name`.
+    pub fn path(&self, name: &str) -> String {
+        let suffix = match self.appearance {
+            Appearance::Dark => &self.dark_suffix,
+            Appearance::Light => &self.light_suffix
+        };
+        let mut file = format!("{}{}", name, suffix);
+        if !self.extension.is_empty() {
+            file.push('.');
+            file.push_str(&self.extension);
+        }
+        if self.directory.is_empty() {
+            file
+        } else {
+            Path::new(&self.directory).join(file).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Returns an `Icon::Path` for the icon variant for `name`.
+    pub fn icon<'a>(&self, name: &str) -> Icon<'a> {
+        Icon::Path(Cow::Owned(self.path(name)))
+    }
+}
+
+#[test]
+fn test_resolver() {
+    let env = Environment::from_map(vec![("alfred_theme_background", "rgba(255,255,255,0.98)")]);
+    let resolver = IconResolver::from_environment(&env);
+    assert_eq!(resolver.appearance(), Appearance::Light);
+    assert_eq!(resolver.path("gear"), "gear-black.png");
+
+    let env = Environment::from_map(vec![("alfred_theme_background", "rgba(30,30,30,0.9)")]);
+    let resolver = IconResolver::from_environment(&env).directory("icons").extension("");
+    assert_eq!(resolver.appearance(), Appearance::Dark);
+    assert_eq!(resolver.path("gear"), "icons/gear-white");
+
+    let resolver = IconResolver::from_environment(&Environment::new())
+                                .dark_suffix("@dark")
+                                .light_suffix("");
+    assert_eq!(resolver.icon("gear"), Icon::Path("gear.png".into()));
+
+    // A nearly transparent background gets classified as neutral gray, which is light.
+    assert_eq!(Appearance::from_color(&Color::new(0, 0, 0, 0.05)), Appearance::Light);
+}
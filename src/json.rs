@@ -1,5 +1,8 @@
 //! Helpers for writing Alfred script filter JSON output (Alfred 3)
 //!
+//! With the `tokio` feature, `write_items_async()` and `Builder::write_async()` write to a tokio
+//! `AsyncWrite` instead.
+//!
 //! # Examples
 //!
 //! ### Writing items
@@ -68,12 +71,20 @@
 //! # }
 //! ```
 
-use ::{Item, ItemType, Modifier, Icon, ModifierData};
+use ::{Action, Item, ItemType, Modifier, Icon, ModifierData};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde_json as json;
 use serde_json::value::Value;
 use std::collections::HashMap;
 use std::io;
 use std::io::prelude::*;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+
+#[cfg(feature = "tokio")]
+use asyncio::WriteAndFlush;
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncWrite;
 
 /// Writes a complete JSON document representing the `Item`s to the `Write`.
 ///
@@ -82,6 +93,19 @@ pub fn write_items<W: Write>(w: W, items: &[Item]) -> io::Result<()> {
     Builder::with_items(items).write(w)
 }
 
+/// Writes a complete JSON document representing the `Item`s to the `AsyncWrite`.
+///
+/// The `AsyncWrite` is flushed after the JSON document is written.
+///
+/// This is the async version of `write_items()`. The items are serialized before this function
+/// returns, so the future doesn't borrow them.
+#[cfg(feature = "tokio")]
+pub fn write_items_async<W>(w: W, items: &[Item]) -> impl Future<Output=io::Result<()>>
+    where W: AsyncWrite + Unpin
+{
+    Builder::with_items(items).write_async(w)
+}
+
 /// A helper type for writing out items with top-level variables.
 ///
 /// Note: If you don't need top-level variables the `write_items()` function is easier to use.
@@ -107,27 +131,34 @@ impl<'a> Builder<'a> {
     /// Writes a complete JSON document representing the items and variables to the `Write`.
     ///
     /// The `Write` is flushed after the JSON document is written.
+    ///
+    /// The JSON is written directly from the items, without building the `Value` returned by
+    /// `into_json()`, but the bytes written are the same.
     pub fn write<W: Write>(self, mut w: W) -> io::Result<()> {
-        write!(&mut w, "{}", self.into_json())?;
+        json::to_writer(&mut w, &self)?;
         w.flush()
     }
 
+    /// Writes a complete JSON document representing the items and variables to the
+    /// `AsyncWrite`.
+    ///
+    /// The `AsyncWrite` is flushed after the JSON document is written.
+    ///
+    /// This is the async version of `write()`. The JSON is serialized before this method returns,
+    /// so the future doesn't borrow the items or variables.
+    #[cfg(feature = "tokio")]
+    pub fn write_async<W>(self, w: W) -> impl Future<Output=io::Result<()>>
+        where W: AsyncWrite + Unpin
+    {
+        let mut buf = Vec::new();
+        // Writing to a Vec can't fail.
+        let _ = json::to_writer(&mut buf, &self);
+        WriteAndFlush::new(w, buf)
+    }
+
     /// Serializes items into their JSON representation.
     pub fn into_json(self) -> Value {
-        let mut root = json::Map::new();
-        root.insert("items".to_string(), Value::Array(self.items.into_iter()
-                                                                .map(|x| x.to_json())
-                                                                .collect()));
-        let mut iter = self.variables.into_iter();
-        if let Some(first) = iter.next() {
-            let mut vars = json::Map::new();
-            vars.insert(first.0.into(), Value::String(first.1.into()));
-            for elt in iter {
-                vars.insert(elt.0.into(), Value::String(elt.1.into()));
-            }
-            root.insert("variables".to_owned(), Value::Object(vars));
-        }
-        Value::Object(root)
+        to_value(&self)
     }
 
     /// Replaces the builder's items with `items`.
@@ -164,111 +195,228 @@ impl<'a> Builder<'a> {
     }
 }
 
-impl<'a> Item<'a> {
-    /// Serializes the `Item` into its JSON representation.
-    pub fn to_json(&self) -> Value {
-        let mut d = json::Map::new();
-        d.insert("title".to_string(), json!(self.title));
-        if let Some(ref subtitle) = self.subtitle {
-            d.insert("subtitle".to_string(), json!(subtitle));
+// The `Serialize` impls below are used both to write JSON directly and to build the `Value`s
+// returned by the `to_json()` methods, so the two always agree. Every map is written with its keys
+// in sorted order, which is also the order `json::Map` uses unless serde_json's `preserve_order`
+// feature is enabled.
+
+/// Converts `value` to a `Value` with its keys in the order they're serialized.
+fn to_value<T: Serialize>(value: T) -> Value {
+    // Serializing strings, booleans, and maps with string keys can't fail.
+    json::to_value(value).expect("items are always serializable")
+}
+
+impl<'a> Serialize for Builder<'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(None)?;
+        map.serialize_entry("items", &Items(self.items))?;
+        if !self.variables.is_empty() {
+            map.serialize_entry("variables", &Variables(&self.variables))?;
+        }
+        map.end()
+    }
+}
+
+struct Items<'r, 'a: 'r>(&'r [Item<'a>]);
+
+impl<'r, 'a> Serialize for Items<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.0.iter().map(ItemRef))
+    }
+}
+
+struct ItemRef<'r, 'a: 'r>(&'r Item<'a>);
+
+impl<'r, 'a> Serialize for ItemRef<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let item = self.0;
+        let mut map = s.serialize_map(None)?;
+        if let Some(ref action) = item.action {
+            map.serialize_entry("action", &ActionRef(action))?;
+        }
+        if let Some(ref arg) = item.arg {
+            map.serialize_entry("arg", arg)?;
+        }
+        if let Some(ref autocomplete) = item.autocomplete {
+            map.serialize_entry("autocomplete", autocomplete)?;
         }
-        if let Some(ref icon) = self.icon {
-            d.insert("icon".to_string(), icon.to_json());
+        if let Some(ref icon) = item.icon {
+            map.serialize_entry("icon", &IconRef(icon))?;
         }
-        if let Some(ref uid) = self.uid {
-            d.insert("uid".to_string(), json!(uid));
+        if !item.modifiers.is_empty() {
+            map.serialize_entry("mods", &Modifiers(&item.modifiers))?;
         }
-        if let Some(ref arg) = self.arg {
-            d.insert("arg".to_string(), json!(arg));
+        if let Some(ref url) = item.quicklook_url {
+            map.serialize_entry("quicklookurl", url)?;
         }
-        match self.type_ {
+        if let Some(ref subtitle) = item.subtitle {
+            map.serialize_entry("subtitle", subtitle)?;
+        }
+        if item.text_copy.is_some() || item.text_large_type.is_some() {
+            map.serialize_entry("text", &Text(item))?;
+        }
+        map.serialize_entry("title", &item.title)?;
+        match item.type_ {
             ItemType::Default => {}
-            ItemType::File => {
-                d.insert("type".to_string(), json!("file"));
-            }
-            ItemType::FileSkipCheck => {
-                d.insert("type".to_string(), json!("file:skipcheck"));
-            }
+            ItemType::File => map.serialize_entry("type", "file")?,
+            ItemType::FileSkipCheck => map.serialize_entry("type", "file:skipcheck")?
         }
-        if !self.valid {
-            d.insert("valid".to_string(), Value::Bool(false));
+        if let Some(ref uid) = item.uid {
+            map.serialize_entry("uid", uid)?;
         }
-        if let Some(ref autocomplete) = self.autocomplete {
-            d.insert("autocomplete".to_string(), json!(autocomplete));
+        if !item.valid {
+            map.serialize_entry("valid", &false)?;
         }
-        if self.text_copy.is_some() || self.text_large_type.is_some() {
-            let mut text = json::Map::new();
-            if let Some(ref text_copy) = self.text_copy {
-                text.insert("copy".to_string(), json!(text_copy));
-            }
-            if let Some(ref text_large_type) = self.text_large_type {
-                text.insert("largetype".to_string(), json!(text_large_type));
-            }
-            d.insert("text".to_string(), Value::Object(text));
+        if !item.variables.is_empty() {
+            map.serialize_entry("variables", &Variables(&item.variables))?;
         }
-        if let Some(ref url) = self.quicklook_url {
-            d.insert("quicklookurl".to_string(), json!(url));
+        map.end()
+    }
+}
+
+struct Text<'r, 'a: 'r>(&'r Item<'a>);
+
+impl<'r, 'a> Serialize for Text<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(None)?;
+        if let Some(ref text_copy) = self.0.text_copy {
+            map.serialize_entry("copy", text_copy)?;
         }
-        if !self.modifiers.is_empty() {
-            let mut mods = json::Map::with_capacity(self.modifiers.len());
-            for (modifier, data) in &self.modifiers {
-                let key = match *modifier {
-                    Modifier::Command => "cmd",
-                    Modifier::Option => "alt",
-                    Modifier::Control => "ctrl",
-                    Modifier::Shift => "shift",
-                    Modifier::Fn => "fn"
-                }.to_string();
-                mods.insert(key, data.to_json());
+        if let Some(ref text_large_type) = self.0.text_large_type {
+            map.serialize_entry("largetype", text_large_type)?;
+        }
+        map.end()
+    }
+}
+
+struct ActionRef<'r, 'a: 'r>(&'r Action<'a>);
+
+impl<'r, 'a> Serialize for ActionRef<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(None)?;
+        if let Some(ref file) = self.0.file {
+            map.serialize_entry("file", file)?;
+        }
+        if let Some(ref text) = self.0.text {
+            map.serialize_entry("text", text)?;
+        }
+        if let Some(ref url) = self.0.url {
+            map.serialize_entry("url", url)?;
+        }
+        map.end()
+    }
+}
+
+struct IconRef<'r, 'a: 'r>(&'r Icon<'a>);
+
+impl<'r, 'a> Serialize for IconRef<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(None)?;
+        match *self.0 {
+            Icon::Path(ref path) => map.serialize_entry("path", path)?,
+            Icon::File(ref path) => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("type", "fileicon")?;
+            }
+            Icon::FileType(ref path) => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("type", "filetype")?;
             }
-            d.insert("mods".to_string(), Value::Object(mods));
         }
-        if !self.variables.is_empty() {
-            let mut vars = json::Map::with_capacity(self.variables.len());
-            for (key, value) in &self.variables {
-                vars.insert(key.clone().into_owned(), json!(value.clone().into_owned()));
+        map.end()
+    }
+}
+
+struct Modifiers<'r, 'a: 'r>(&'r HashMap<Modifier, ModifierData<'a>>);
+
+impl<'r, 'a> Serialize for Modifiers<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        // Sorted by key.
+        const KEYS: [(Modifier, &str); 5] = [(Modifier::Option, "alt"), (Modifier::Command, "cmd"),
+                                             (Modifier::Control, "ctrl"), (Modifier::Fn, "fn"),
+                                             (Modifier::Shift, "shift")];
+        let mut map = s.serialize_map(None)?;
+        for &(modifier, key) in &KEYS {
+            if let Some(data) = self.0.get(&modifier) {
+                map.serialize_entry(key, &ModifierDataRef(data))?;
             }
-            d.insert("variables".to_string(), Value::Object(vars));
         }
-        Value::Object(d)
+        map.end()
+    }
+}
+
+struct ModifierDataRef<'r, 'a: 'r>(&'r ModifierData<'a>);
+
+impl<'r, 'a> Serialize for ModifierDataRef<'r, 'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let data = self.0;
+        let mut map = s.serialize_map(None)?;
+        if let Some(ref arg) = data.arg {
+            map.serialize_entry("arg", arg)?;
+        }
+        if let Some(ref icon) = data.icon {
+            map.serialize_entry("icon", &IconRef(icon))?;
+        }
+        if let Some(ref subtitle) = data.subtitle {
+            map.serialize_entry("subtitle", subtitle)?;
+        }
+        if let Some(valid) = data.valid {
+            map.serialize_entry("valid", &valid)?;
+        }
+        if !data.variables.is_empty() {
+            map.serialize_entry("variables", &Variables(&data.variables))?;
+        }
+        map.end()
+    }
+}
+
+struct Variables<'r, K: 'r, V: 'r>(&'r HashMap<K, V>);
+
+impl<'r, K, V> Serialize for Variables<'r, K, V>
+    where K: AsRef<str> + Eq + ::std::hash::Hash,
+          V: AsRef<str>
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut vars: Vec<(&str, &str)> = self.0.iter().map(|(k, v)| (k.as_ref(), v.as_ref()))
+                                                  .collect();
+        vars.sort_unstable_by_key(|&(k, _)| k);
+        s.collect_map(vars)
+    }
+}
+
+impl<'a> Item<'a> {
+    /// Writes the JSON representation of the `Item` to the `Write`.
+    ///
+    /// This writes the same bytes as formatting the result of `to_json()`, without building the
+    /// intermediate `Value`.
+    pub fn write_json(&self, w: &mut dyn Write) -> io::Result<()> {
+        json::to_writer(w, &ItemRef(self)).map_err(io::Error::from)
+    }
+
+    /// Serializes the `Item` into its JSON representation.
+    pub fn to_json(&self) -> Value {
+        to_value(ItemRef(self))
+    }
+}
+
+impl<'a> Action<'a> {
+    /// Serializes the `Action` into its JSON representation.
+    pub fn to_json(&self) -> Value {
+        to_value(ActionRef(self))
     }
 }
 
 impl<'a> Icon<'a> {
     /// Serializes the `Icon` into its JSON representation.
     pub fn to_json(&self) -> Value {
-        match *self {
-            Icon::Path(ref s) => json!({"path": s}),
-            Icon::File(ref s) => json!({"type": "fileicon", "path": s}),
-            Icon::FileType(ref s) => json!({"type": "filetype", "path": s})
-        }
+        to_value(IconRef(self))
     }
 }
 
 impl<'a> ModifierData<'a> {
     /// Serializes the `ModifierData` into its JSON representation.
     pub fn to_json(&self) -> Value {
-        let mut mod_ = json::Map::new();
-        if let Some(ref subtitle) = self.subtitle {
-            mod_.insert("subtitle".to_string(), json!(subtitle));
-        }
-        if let Some(ref arg) = self.arg {
-            mod_.insert("arg".to_string(), json!(arg));
-        }
-        if let Some(valid) = self.valid {
-            mod_.insert("valid".to_string(), json!(valid));
-        }
-        if let Some(ref icon) = self.icon {
-            mod_.insert("icon".to_string(), icon.to_json());
-        }
-        if !self.variables.is_empty() {
-            let mut vars = json::Map::with_capacity(self.variables.len());
-            for (key, value) in &self.variables {
-                vars.insert(key.clone().into_owned(), json!(value.clone().into_owned()));
-            }
-            mod_.insert("variables".to_string(), Value::Object(vars));
-        }
-        Value::Object(mod_)
+        to_value(ModifierDataRef(self))
     }
 }
 
@@ -402,3 +550,92 @@ fn test_builder() {
                    }
                }));
 }
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_write_async() {
+    use asyncio::{block_on, Trickle};
+
+    let items = [Item::new("Item 1"), ::ItemBuilder::new("Ünïcode").arg("\"arg\"").into_item()];
+    let mut expected = Vec::new();
+    Builder::with_items(&items).variable("key", "value").write(&mut expected).unwrap();
+
+    let mut output = Trickle::default();
+    let future = Builder::with_items(&items).variable("key", "value").write_async(&mut output);
+    block_on(future).unwrap();
+    assert_eq!(output.data, expected);
+    assert!(output.flushed);
+
+    let mut output = Vec::new();
+    block_on(write_items_async(&mut output, &items)).unwrap();
+    let mut expected = Vec::new();
+    write_items(&mut expected, &items).unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_write_matches_into_json() {
+    let items = [
+        Item::new("Item 1"),
+        ::ItemBuilder::new("Ünïcode \"quotes\" \\ /\n\t\u{1}\u{7f}\u{2028}")
+                      .subtitle("Subtitle")
+                      .uid("uid")
+                      .arg("arg")
+                      .type_(ItemType::FileSkipCheck)
+                      .valid(false)
+                      .autocomplete("auto")
+                      .text_copy("copy")
+                      .text_large_type("large")
+                      .quicklook_url("https://example.com")
+                      .action_text("text")
+                      .action_url("https://example.com")
+                      .action_file("/tmp")
+                      .icon_file("/Applications")
+                      .variable("b", "2")
+                      .variable("a", "1")
+                      .variable("", "")
+                      .subtitle_mod(Modifier::Command, "cmd")
+                      .arg_mod(Modifier::Option, "alt")
+                      .valid_mod(Modifier::Control, true)
+                      .icon_path_mod(Modifier::Fn, "fn.png")
+                      .variable_mod(Modifier::Shift, "y", "2")
+                      .variable_mod(Modifier::Shift, "x", "1")
+                      .into_item(),
+        ::ItemBuilder::new("Item 3").type_(ItemType::File).icon_filetype("public.folder")
+                                    .text_large_type("large").into_item()
+    ];
+    for builder in [Builder::new(), Builder::with_items(&items),
+                   Builder::with_items(&items).variable("z", "26").variable("a", "1")] {
+        let mut output = Vec::new();
+        builder.clone().write(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), builder.into_json().to_string());
+    }
+    for item in &items {
+        let mut output = Vec::new();
+        item.write_json(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), item.to_json().to_string());
+    }
+
+    // Both paths write keys in sorted order, whichever serde_json features are enabled.
+    let expected = concat!(
+        r#"{"action":{"file":"/tmp","text":"text","url":"https://example.com"},"arg":"arg","#,
+        r#""autocomplete":"auto","icon":{"path":"/Applications","type":"fileicon"},"#,
+        r#""mods":{"alt":{"arg":"alt"},"cmd":{"subtitle":"cmd"},"ctrl":{"valid":true},"#,
+        r#""fn":{"icon":{"path":"fn.png"}},"shift":{"variables":{"x":"1","y":"2"}}},"#,
+        r#""quicklookurl":"https://example.com","subtitle":"Subtitle","#,
+        r#""text":{"copy":"copy","largetype":"large"},"#,
+        "\"title\":\"Ünïcode \\\"quotes\\\" \\\\ /\\n\\t\\u0001\u{7f}\u{2028}\",",
+        r#""type":"file:skipcheck","uid":"uid","valid":false,"#,
+        r#""variables":{"":"","a":"1","b":"2"}}"#
+    );
+    assert_eq!(items[1].to_json().to_string(), expected);
+    let mut output = Vec::new();
+    items[1].write_json(&mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+    let builder = Builder::with_items(&items[..1]).variable("z", "26").variable("a", "1");
+    let expected = r#"{"items":[{"title":"Item 1"}],"variables":{"a":"1","z":"26"}}"#;
+    assert_eq!(builder.clone().into_json().to_string(), expected);
+    let mut output = Vec::new();
+    builder.write(&mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+}
@@ -84,7 +84,7 @@
 //! # use std::io::{self, Write};
 //! #
 //! # fn write_items() -> io::Result<()> {
-//! let mut xmlw = try!(alfred::XMLWriter::new(io::stdout()));
+//! let mut xmlw = alfred::XMLWriter::new(io::stdout())?;
 //!
 //! let item1 = alfred::Item::new("Item 1");
 //! let item2 = alfred::ItemBuilder::new("Item 2")
@@ -96,11 +96,11 @@
 //!                                 .icon_filetype("public.folder")
 //!                                 .into_item();
 //!
-//! try!(xmlw.write_item(&item1));
-//! try!(xmlw.write_item(&item2));
-//! try!(xmlw.write_item(&item3));
+//! xmlw.write_item(&item1)?;
+//! xmlw.write_item(&item2)?;
+//! xmlw.write_item(&item3)?;
 //!
-//! let mut stdout = try!(xmlw.close());
+//! let mut stdout = xmlw.close()?;
 //! stdout.flush()
 //! # }
 //! #
@@ -118,22 +118,64 @@
 
 #![doc(html_root_url = "https://docs.rs/alfred/4.0.2")]
 
+extern crate log;
+#[macro_use]
+extern crate serde;
 #[macro_use]
 extern crate serde_json;
+#[cfg(any(feature = "package", feature = "workflow"))]
+extern crate plist;
+#[cfg(feature = "package")]
+extern crate zip;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
 pub mod json;
+pub mod textview;
 pub mod xml;
 pub mod env;
+pub mod color;
+pub mod icon;
+pub mod config;
+pub mod runner;
+pub mod crash;
+pub mod logger;
+pub mod update;
+pub mod pagination;
+pub mod collection;
+pub mod deadline;
+pub mod aggregate;
+pub mod urlscheme;
+pub mod escaping;
+pub mod router;
+#[cfg(feature = "package")]
+pub mod package;
+#[cfg(feature = "workflow")]
+pub mod workflow;
+#[cfg(feature = "workflow")]
+pub mod userconfig;
+#[cfg(feature = "tokio")]
+mod asyncio;
 
 use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
+use std::error;
+use std::fmt;
 use std::iter::FromIterator;
 use std::hash::Hash;
+use std::path::{Path, PathBuf};
 
 pub use self::xml::XMLWriter;
+#[cfg(feature = "tokio")]
+pub use self::xml::AsyncXMLWriter;
+pub use self::collection::Items;
 
 /// Representation of a script filter item.
 #[derive(Clone,Debug,PartialEq,Eq)]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct Item<'a> {
     /// Title for the item.
     pub title: Cow<'a, str>,
@@ -181,6 +223,10 @@ pub struct Item<'a> {
     pub text_large_type: Option<Cow<'a, str>>,
     /// A URL to use for Quick Look.
     pub quicklook_url: Option<Cow<'a, str>>,
+    /// The values Universal Actions act on when the item is selected.
+    ///
+    /// This property is only used with JSON output and only affects Alfred 4.5 or later.
+    pub action: Option<Action<'a>>,
 
     /// Optional overrides of subtitle, arg, and valid by modifiers.
     pub modifiers: HashMap<Modifier, ModifierData<'a>>,
@@ -209,11 +255,78 @@ impl<'a> Item<'a> {
             text_copy: None,
             text_large_type: None,
             quicklook_url: None,
+            action: None,
             modifiers: HashMap::new(),
             variables: HashMap::new(),
             _priv: ()
         }
     }
+
+    /// Converts the `Item` into one that owns all of its strings.
+    ///
+    /// Strings that are already owned are moved rather than copied. The result can be stored in
+    /// a cache, sent to another thread, or returned from a closure.
+    pub fn into_owned(self) -> Item<'static> {
+        Item {
+            title: owned(self.title),
+            subtitle: self.subtitle.map(owned),
+            icon: self.icon.map(Icon::into_owned),
+            uid: self.uid.map(owned),
+            arg: self.arg.map(owned),
+            type_: self.type_,
+            valid: self.valid,
+            autocomplete: self.autocomplete.map(owned),
+            text_copy: self.text_copy.map(owned),
+            text_large_type: self.text_large_type.map(owned),
+            quicklook_url: self.quicklook_url.map(owned),
+            action: self.action.map(Action::into_owned),
+            modifiers: self.modifiers.into_iter().map(|(m, d)| (m, d.into_owned())).collect(),
+            variables: owned_variables(self.variables),
+            _priv: ()
+        }
+    }
+
+    /// Returns a copy of the `Item` that owns all of its strings.
+    pub fn to_owned(&self) -> Item<'static> {
+        self.as_borrowed().into_owned()
+    }
+
+    /// Returns a copy of the `Item` that borrows all of its strings from this one.
+    ///
+    /// This is cheaper than `clone()` when the strings are owned.
+    pub fn as_borrowed(&self) -> Item<'_> {
+        Item {
+            title: Cow::Borrowed(&self.title),
+            subtitle: self.subtitle.as_deref().map(Cow::Borrowed),
+            icon: self.icon.as_ref().map(Icon::as_borrowed),
+            uid: self.uid.as_deref().map(Cow::Borrowed),
+            arg: self.arg.as_deref().map(Cow::Borrowed),
+            type_: self.type_,
+            valid: self.valid,
+            autocomplete: self.autocomplete.as_deref().map(Cow::Borrowed),
+            text_copy: self.text_copy.as_deref().map(Cow::Borrowed),
+            text_large_type: self.text_large_type.as_deref().map(Cow::Borrowed),
+            quicklook_url: self.quicklook_url.as_deref().map(Cow::Borrowed),
+            action: self.action.as_ref().map(Action::as_borrowed),
+            modifiers: self.modifiers.iter().map(|(&m, d)| (m, d.as_borrowed())).collect(),
+            variables: borrowed_variables(&self.variables),
+            _priv: ()
+        }
+    }
+}
+
+fn owned(s: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
+}
+
+fn owned_variables(vars: HashMap<Cow<str>, Cow<str>>)
+                   -> HashMap<Cow<'static, str>, Cow<'static, str>> {
+    vars.into_iter().map(|(k, v)| (owned(k), owned(v))).collect()
+}
+
+fn borrowed_variables<'b>(vars: &'b HashMap<Cow<str>, Cow<str>>)
+                          -> HashMap<Cow<'b, str>, Cow<'b, str>> {
+    vars.iter().map(|(k, v)| (Cow::Borrowed(&**k), Cow::Borrowed(&**v))).collect()
 }
 
 /// Helper for building `Item` values.
@@ -230,12 +343,175 @@ impl<'a> ItemBuilder<'a> {
         }
     }
 
+    /// Returns a new `ItemBuilder` for the file or folder at `path`.
+    ///
+    /// The title is the file name and the subtitle is the parent folder, with the home folder
+    /// abbreviated as `~`. The path is used as the `arg`, `uid`, and `quicklook_url`, the icon is
+    /// the file's icon, and the type is `ItemType::File`, so Alfred hides the item if the file
+    /// doesn't exist and offers its file actions. The path should be absolute.
+    ///
+    /// Alfred's output formats can't represent paths that aren't valid UTF-8, so this returns an
+    /// error for them rather than mangling the path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ItemBuilder<'a>, NonUnicodePathError> {
+        let home = ::std::env::var_os("HOME").map(PathBuf::from);
+        ItemBuilder::from_path_with_home(path.as_ref(), home.as_deref())
+    }
+
+    /// Returns a new `ItemBuilder` for the file or folder at `path`, with the type
+    /// `ItemType::FileSkipCheck`.
+    ///
+    /// This is the same as `from_path()`, except that Alfred doesn't check that the file exists,
+    /// which is faster when listing many files that are known to exist.
+    pub fn from_path_skip_check<P: AsRef<Path>>(path: P)
+                                               -> Result<ItemBuilder<'a>, NonUnicodePathError> {
+        Ok(ItemBuilder::from_path(path)?.type_(ItemType::FileSkipCheck))
+    }
+
+    fn from_path_with_home(path: &Path, home: Option<&Path>)
+                          -> Result<ItemBuilder<'a>, NonUnicodePathError> {
+        let path_str = match path.to_str() {
+            Some(s) => s.to_owned(),
+            None => return Err(NonUnicodePathError { path: path.to_owned() })
+        };
+        // The file name and parent of a UTF-8 path are valid UTF-8 too.
+        let title = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => path_str.clone()
+        };
+        let mut builder = ItemBuilder::new(title)
+                                      .arg(path_str.clone())
+                                      .uid(path_str.clone())
+                                      .quicklook_url(path_str.clone())
+                                      .icon_file(path_str)
+                                      .type_(ItemType::File);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            let subtitle = match home.and_then(|home| parent.strip_prefix(home).ok()) {
+                Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+                Some(rest) => format!("~/{}", rest.to_string_lossy()),
+                None => parent.to_string_lossy().into_owned()
+            };
+            builder.set_subtitle(subtitle);
+        }
+        Ok(builder)
+    }
+
+    /// Returns a new `ItemBuilder` for a link to `url`.
+    ///
+    /// The subtitle is the URL's host, or the whole URL if it has no host (e.g. `mailto:`
+    /// URLs). The URL is used as the `arg`, `uid`, `quicklook_url`, and the text that's copied.
+    /// Use `icon_path()` to add a favicon, and `action_url()` to offer Universal Actions for the
+    /// URL.
+    ///
+    /// This returns an error if `url` isn't an absolute URL, or if it contains whitespace.
+    pub fn from_url<T, U>(title: T, url: U) -> Result<ItemBuilder<'a>, InvalidUrlError>
+        where T: Into<Cow<'a, str>>,
+              U: Into<Cow<'a, str>>
+    {
+        let url = url.into();
+        let subtitle = match url_host(&url) {
+            Some(Some(host)) => host.to_owned(),
+            Some(None) => url.clone().into_owned(),
+            None => return Err(InvalidUrlError { url: url.into_owned() })
+        };
+        Ok(ItemBuilder::new(title)
+                       .subtitle(subtitle)
+                       .arg(url.clone())
+                       .uid(url.clone())
+                       .quicklook_url(url.clone())
+                       .text_copy(url))
+    }
+
     /// Returns the built `Item`.
     pub fn into_item(self) -> Item<'a> {
         self.item
     }
 }
 
+/// An error returned when a path can't be used in an item because it isn't valid UTF-8.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct NonUnicodePathError {
+    path: PathBuf
+}
+
+impl NonUnicodePathError {
+    /// Returns the path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path, consuming the error.
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl fmt::Display for NonUnicodePathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "path is not valid UTF-8: {}", self.path.display())
+    }
+}
+
+impl error::Error for NonUnicodePathError {}
+
+/// An error returned by `ItemBuilder::from_url()` when the URL isn't valid.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct InvalidUrlError {
+    url: String
+}
+
+impl InvalidUrlError {
+    /// Returns the invalid URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl fmt::Display for InvalidUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid URL: {}", self.url)
+    }
+}
+
+impl error::Error for InvalidUrlError {}
+
+/// Checks that `url` is an absolute URL and returns its host, if it has one.
+///
+/// Returns `None` if the URL is invalid.
+fn url_host(url: &str) -> Option<Option<&str>> {
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return None;
+    }
+    let colon = url.find(':')?;
+    let scheme = &url[..colon];
+    let mut chars = scheme.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+       || !chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+        return None;
+    }
+    let rest = &url[colon+1..];
+    let rest = match rest.strip_prefix("//") {
+        Some(rest) => rest,
+        None if rest.is_empty() => return None,
+        None => {
+            let needs_host = ["http", "https", "ftp"].iter()
+                                                     .any(|s| scheme.eq_ignore_ascii_case(s));
+            return if needs_host { None } else { Some(None) };
+        }
+    };
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let host = authority.rsplit('@').next().unwrap_or("");
+    let host = if host.starts_with('[') {
+        &host[..host.find(']')? + 1]
+    } else {
+        host.split(':').next().unwrap_or("")
+    };
+    match host {
+        "" if scheme.eq_ignore_ascii_case("file") => Some(None),
+        "" => None,
+        host => Some(Some(host))
+    }
+}
+
 impl<'a> ItemBuilder<'a> {
     /// Sets the `title` to the given value.
     pub fn title<S: Into<Cow<'a, str>>>(mut self, title: S) -> ItemBuilder<'a> {
@@ -261,6 +537,12 @@ impl<'a> ItemBuilder<'a> {
         self
     }
 
+    /// Sets the `icon` to the given value.
+    pub fn icon(mut self, icon: Icon<'a>) -> ItemBuilder<'a> {
+        self.set_icon(icon);
+        self
+    }
+
     /// Sets the `icon` to an image file on disk.
     ///
     /// The path is interpreted relative to the workflow directory.
@@ -369,6 +651,7 @@ impl<'a> ItemBuilder<'a> {
     }
 
     /// Sets the subtitle, arg, validity, and icon to use with the given modifier.
+    #[deprecated(note = "use `modifier_data()` with a `ModifierBuilder` instead")]
     pub fn modifier<S: Into<Cow<'a, str>>, S2: Into<Cow<'a, str>>>(mut self,
                                                                    modifier: Modifier,
                                                                    subtitle: Option<S>,
@@ -376,10 +659,20 @@ impl<'a> ItemBuilder<'a> {
                                                                    valid: bool,
                                                                    icon: Option<Icon<'a>>)
                                                                   -> ItemBuilder<'a> {
+        #[allow(deprecated)]
         self.set_modifier(modifier, subtitle, arg, valid, icon);
         self
     }
 
+    /// Sets the data to use with the given modifier, replacing any existing data.
+    ///
+    /// `data` is usually built with a `ModifierBuilder`.
+    pub fn modifier_data<D: Into<ModifierData<'a>>>(mut self, modifier: Modifier, data: D)
+                                                   -> ItemBuilder<'a> {
+        self.set_modifier_data(modifier, data);
+        self
+    }
+
     /// Sets `autocomplete` to the given value.
     pub fn autocomplete<S: Into<Cow<'a, str>>>(mut self, autocomplete: S) -> ItemBuilder<'a> {
         self.set_autocomplete(autocomplete);
@@ -404,6 +697,30 @@ impl<'a> ItemBuilder<'a> {
         self
     }
 
+    /// Sets the text that Universal Actions act on.
+    ///
+    /// Universal Actions are only used with JSON output and only affect Alfred 4.5 or later.
+    pub fn action_text<S: Into<Cow<'a, str>>>(mut self, text: S) -> ItemBuilder<'a> {
+        self.set_action_text(text);
+        self
+    }
+
+    /// Sets the URL that Universal Actions act on.
+    ///
+    /// Universal Actions are only used with JSON output and only affect Alfred 4.5 or later.
+    pub fn action_url<S: Into<Cow<'a, str>>>(mut self, url: S) -> ItemBuilder<'a> {
+        self.set_action_url(url);
+        self
+    }
+
+    /// Sets the path of the file that Universal Actions act on.
+    ///
+    /// Universal Actions are only used with JSON output and only affect Alfred 4.5 or later.
+    pub fn action_file<S: Into<Cow<'a, str>>>(mut self, path: S) -> ItemBuilder<'a> {
+        self.set_action_file(path);
+        self
+    }
+
     /// Inserts a key/value pair into the item variables.
     ///
     /// Item variables are only used with JSON output and only affect Alfred 3.4.1 or later.
@@ -495,6 +812,11 @@ impl<'a> ItemBuilder<'a> {
         }
     }
 
+    /// Sets the `icon` to the given value.
+    pub fn set_icon(&mut self, icon: Icon<'a>) {
+        self.item.icon = Some(icon);
+    }
+
     /// Sets the `icon` to an image file on disk.
     ///
     /// The path is interpreted relative to the workflow directory.
@@ -680,6 +1002,7 @@ impl<'a> ItemBuilder<'a> {
     }
 
     /// Sets subtitle, arg, validity, and icon for the given modifier.
+    #[deprecated(note = "use `set_modifier_data()` with a `ModifierBuilder` instead")]
     pub fn set_modifier<S: Into<Cow<'a, str>>, S2: Into<Cow<'a, str>>>(&mut self,
                                                                        modifier: Modifier,
                                                                        subtitle: Option<S>,
@@ -690,13 +1013,26 @@ impl<'a> ItemBuilder<'a> {
             subtitle: subtitle.map(Into::into),
             arg: arg.map(Into::into),
             valid: Some(valid),
-            icon: icon,
+            icon,
             variables: HashMap::new(),
             _priv: ()
         };
         self.item.modifiers.insert(modifier, data);
     }
 
+    /// Sets the data to use with the given modifier, replacing any existing data.
+    ///
+    /// `data` is usually built with a `ModifierBuilder`. If it's empty, this is the same as
+    /// `unset_modifier()`.
+    pub fn set_modifier_data<D: Into<ModifierData<'a>>>(&mut self, modifier: Modifier, data: D) {
+        let data = data.into();
+        if data.is_empty() {
+            self.unset_modifier(modifier);
+        } else {
+            self.item.modifiers.insert(modifier, data);
+        }
+    }
+
     /// Unsets subtitle, arg, and validity for the given modifier.
     pub fn unset_modifier(&mut self, modifier: Modifier) {
         self.item.modifiers.remove(&modifier);
@@ -732,6 +1068,26 @@ impl<'a> ItemBuilder<'a> {
         self.item.quicklook_url = None;
     }
 
+    /// Sets the text that Universal Actions act on.
+    pub fn set_action_text<S: Into<Cow<'a, str>>>(&mut self, text: S) {
+        self.item.action.get_or_insert_with(Action::default).text = Some(text.into());
+    }
+
+    /// Sets the URL that Universal Actions act on.
+    pub fn set_action_url<S: Into<Cow<'a, str>>>(&mut self, url: S) {
+        self.item.action.get_or_insert_with(Action::default).url = Some(url.into());
+    }
+
+    /// Sets the path of the file that Universal Actions act on.
+    pub fn set_action_file<S: Into<Cow<'a, str>>>(&mut self, path: S) {
+        self.item.action.get_or_insert_with(Action::default).file = Some(path.into());
+    }
+
+    /// Unsets `action`.
+    pub fn unset_action(&mut self) {
+        self.item.action = None;
+    }
+
     /// Inserts a key/value pair into the item variables.
     ///
     /// Item variables are only used with JSON output and only affect Alfred 3.4.1 or later.
@@ -745,9 +1101,9 @@ impl<'a> ItemBuilder<'a> {
     /// Removes a key from the item variables.
     ///
     /// Item variables are only used with JSON output and only affect Alfred 3.4.1 or later.
-    pub fn unset_variable<K: ?Sized>(&mut self, key: &K)
+    pub fn unset_variable<K>(&mut self, key: &K)
         where Cow<'a, str>: Borrow<K>,
-              K: Hash + Eq
+              K: Hash + Eq + ?Sized
     {
         self.item.variables.remove(key);
     }
@@ -786,9 +1142,9 @@ impl<'a> ItemBuilder<'a> {
     /// Removes a key from the variables for the given modifier.
     ///
     /// Item variables are only used with JSON output and only affect Alfred 3.4.1 or later.
-    pub fn unset_variable_mod<K: ?Sized>(&mut self, modifier: Modifier, key: &K)
+    pub fn unset_variable_mod<K>(&mut self, modifier: Modifier, key: &K)
         where Cow<'a, str>: Borrow<K>,
-              K: Hash + Eq
+              K: Hash + Eq + ?Sized
     {
         use std::collections::hash_map::Entry;
         if let Entry::Occupied(mut entry) = self.item.modifiers.entry(modifier) {
@@ -835,7 +1191,7 @@ impl<'a> ItemBuilder<'a> {
     }
 
     fn data_for_modifier(&mut self, modifier: Modifier) -> &mut ModifierData<'a> {
-        self.item.modifiers.entry(modifier).or_insert_with(Default::default)
+        self.item.modifiers.entry(modifier).or_default()
     }
 }
 
@@ -855,11 +1211,12 @@ pub enum Modifier {
     Fn
 }
 
-const ALL_MODIFIERS: &'static [Modifier] = &[Modifier::Command, Modifier::Option,
+const ALL_MODIFIERS: &[Modifier] = &[Modifier::Command, Modifier::Option,
                                              Modifier::Control, Modifier::Shift, Modifier::Fn];
 
 /// Optional overrides of subtitle, arg, and valid for modifiers.
 #[derive(Clone,Debug,PartialEq,Eq,Default)]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct ModifierData<'a> {
     /// The subtitle to use for the current modifier.
     pub subtitle: Option<Cow<'a, str>>,
@@ -891,6 +1248,37 @@ impl<'a> ModifierData<'a> {
         Default::default()
     }
 
+    /// Converts the `ModifierData` into one that owns all of its strings.
+    ///
+    /// See `Item::into_owned()`.
+    pub fn into_owned(self) -> ModifierData<'static> {
+        ModifierData {
+            subtitle: self.subtitle.map(owned),
+            arg: self.arg.map(owned),
+            valid: self.valid,
+            icon: self.icon.map(Icon::into_owned),
+            variables: owned_variables(self.variables),
+            _priv: ()
+        }
+    }
+
+    /// Returns a copy of the `ModifierData` that owns all of its strings.
+    pub fn to_owned(&self) -> ModifierData<'static> {
+        self.as_borrowed().into_owned()
+    }
+
+    /// Returns a copy of the `ModifierData` that borrows all of its strings from this one.
+    pub fn as_borrowed(&self) -> ModifierData<'_> {
+        ModifierData {
+            subtitle: self.subtitle.as_deref().map(Cow::Borrowed),
+            arg: self.arg.as_deref().map(Cow::Borrowed),
+            valid: self.valid,
+            icon: self.icon.as_ref().map(Icon::as_borrowed),
+            variables: borrowed_variables(&self.variables),
+            _priv: ()
+        }
+    }
+
     fn is_empty(&self) -> bool {
         self.subtitle.is_none()
         && self.arg.is_none()
@@ -900,6 +1288,242 @@ impl<'a> ModifierData<'a> {
     }
 }
 
+/// Helper for building `ModifierData` values.
+///
+/// # Example
+///
+/// ```
+/// # extern crate alfred;
+/// # fn main() {
+/// use alfred::{ItemBuilder, Modifier, ModifierBuilder};
+///
+/// let reveal = ModifierBuilder::new()
+///                              .subtitle("Reveal in Finder")
+///                              .variable("action", "reveal");
+/// let item = ItemBuilder::new("notes.txt")
+///                        .arg("/tmp/notes.txt")
+///                        .modifier_data(Modifier::Command, reveal)
+///                        .into_item();
+/// assert_eq!(item.modifiers[&Modifier::Command].subtitle.as_deref(), Some("Reveal in Finder"));
+/// # }
+/// ```
+#[derive(Clone,Debug,Default)]
+pub struct ModifierBuilder<'a> {
+    data: ModifierData<'a>
+}
+
+impl<'a> ModifierBuilder<'a> {
+    /// Returns a new `ModifierBuilder` where all fields are unset.
+    pub fn new() -> ModifierBuilder<'a> {
+        Default::default()
+    }
+
+    /// Returns the built `ModifierData`.
+    pub fn into_modifier_data(self) -> ModifierData<'a> {
+        self.data
+    }
+
+    /// Sets the `subtitle` to the given value.
+    pub fn subtitle<S: Into<Cow<'a, str>>>(mut self, subtitle: S) -> ModifierBuilder<'a> {
+        self.set_subtitle(subtitle);
+        self
+    }
+
+    /// Sets the `arg` to the given value.
+    pub fn arg<S: Into<Cow<'a, str>>>(mut self, arg: S) -> ModifierBuilder<'a> {
+        self.set_arg(arg);
+        self
+    }
+
+    /// Sets `valid` to the given value.
+    pub fn valid(mut self, valid: bool) -> ModifierBuilder<'a> {
+        self.set_valid(valid);
+        self
+    }
+
+    /// Sets the `icon` to the given value.
+    ///
+    /// Modifier icons are only used with JSON output and only affect Alfred 3.4.1 or later.
+    pub fn icon(mut self, icon: Icon<'a>) -> ModifierBuilder<'a> {
+        self.set_icon(icon);
+        self
+    }
+
+    /// Sets the `icon` to an image file on disk.
+    ///
+    /// The path is interpreted relative to the workflow directory.
+    pub fn icon_path<S: Into<Cow<'a, str>>>(self, path: S) -> ModifierBuilder<'a> {
+        self.icon(Icon::Path(path.into()))
+    }
+
+    /// Sets the `icon` to the icon for a given file on disk.
+    ///
+    /// The path is interpreted relative to the workflow directory.
+    pub fn icon_file<S: Into<Cow<'a, str>>>(self, path: S) -> ModifierBuilder<'a> {
+        self.icon(Icon::File(path.into()))
+    }
+
+    /// Sets the `icon` to the icon for a given file type.
+    ///
+    /// The type is a UTI, such as "public.jpeg".
+    pub fn icon_filetype<S: Into<Cow<'a, str>>>(self, filetype: S) -> ModifierBuilder<'a> {
+        self.icon(Icon::FileType(filetype.into()))
+    }
+
+    /// Inserts a key/value pair into the variables.
+    ///
+    /// Variables are only used with JSON output and only affect Alfred 3.4.1 or later.
+    pub fn variable<K,V>(mut self, key: K, value: V) -> ModifierBuilder<'a>
+        where K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.set_variable(key, value);
+        self
+    }
+
+    /// Sets the variables to `variables`.
+    ///
+    /// Variables are only used with JSON output and only affect Alfred 3.4.1 or later.
+    pub fn variables<I,K,V>(mut self, variables: I) -> ModifierBuilder<'a>
+        where I: IntoIterator<Item=(K,V)>,
+              K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.set_variables(variables);
+        self
+    }
+}
+
+impl<'a> ModifierBuilder<'a> {
+    /// Sets the `subtitle` to the given value.
+    pub fn set_subtitle<S: Into<Cow<'a, str>>>(&mut self, subtitle: S) {
+        self.data.subtitle = Some(subtitle.into());
+    }
+
+    /// Unsets the `subtitle`.
+    pub fn unset_subtitle(&mut self) {
+        self.data.subtitle = None;
+    }
+
+    /// Sets the `arg` to the given value.
+    pub fn set_arg<S: Into<Cow<'a, str>>>(&mut self, arg: S) {
+        self.data.arg = Some(arg.into());
+    }
+
+    /// Unsets the `arg`.
+    pub fn unset_arg(&mut self) {
+        self.data.arg = None;
+    }
+
+    /// Sets `valid` to the given value.
+    pub fn set_valid(&mut self, valid: bool) {
+        self.data.valid = Some(valid);
+    }
+
+    /// Unsets `valid`.
+    pub fn unset_valid(&mut self) {
+        self.data.valid = None;
+    }
+
+    /// Sets the `icon` to the given value.
+    pub fn set_icon(&mut self, icon: Icon<'a>) {
+        self.data.icon = Some(icon);
+    }
+
+    /// Unsets the `icon`.
+    pub fn unset_icon(&mut self) {
+        self.data.icon = None;
+    }
+
+    /// Inserts a key/value pair into the variables.
+    pub fn set_variable<K,V>(&mut self, key: K, value: V)
+        where K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.data.variables.insert(key.into(), value.into());
+    }
+
+    /// Removes a key from the variables.
+    pub fn unset_variable<K>(&mut self, key: &K)
+        where Cow<'a, str>: Borrow<K>,
+              K: Hash + Eq + ?Sized
+    {
+        self.data.variables.remove(key);
+    }
+
+    /// Sets the variables to `variables`.
+    pub fn set_variables<I,K,V>(&mut self, variables: I)
+        where I: IntoIterator<Item=(K,V)>,
+              K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.data.variables = HashMap::from_iter(variables.into_iter()
+                                                          .map(|(k,v)| (k.into(),v.into())));
+    }
+
+    /// Removes all variables.
+    pub fn unset_variables(&mut self) {
+        self.data.variables.clear()
+    }
+}
+
+impl<'a> From<ModifierBuilder<'a>> for ModifierData<'a> {
+    fn from(builder: ModifierBuilder<'a>) -> ModifierData<'a> {
+        builder.into_modifier_data()
+    }
+}
+
+/// The values Universal Actions act on when an item is selected.
+///
+/// When `action` is unset, Universal Actions act on the item's `arg`.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct Action<'a> {
+    /// Text to act on.
+    pub text: Option<Cow<'a, str>>,
+    /// A URL to act on.
+    pub url: Option<Cow<'a, str>>,
+    /// The path of a file to act on.
+    pub file: Option<Cow<'a, str>>,
+
+    /// Disallow struct literals for `Action`.
+    _priv: ()
+}
+
+impl<'a> Action<'a> {
+    /// Returns a new `Action` where all fields are `None`.
+    pub fn new() -> Action<'a> {
+        Default::default()
+    }
+
+    /// Converts the `Action` into one that owns all of its strings.
+    ///
+    /// See `Item::into_owned()`.
+    pub fn into_owned(self) -> Action<'static> {
+        Action {
+            text: self.text.map(owned),
+            url: self.url.map(owned),
+            file: self.file.map(owned),
+            _priv: ()
+        }
+    }
+
+    /// Returns a copy of the `Action` that owns all of its strings.
+    pub fn to_owned(&self) -> Action<'static> {
+        self.as_borrowed().into_owned()
+    }
+
+    /// Returns a copy of the `Action` that borrows all of its strings from this one.
+    pub fn as_borrowed(&self) -> Action<'_> {
+        Action {
+            text: self.text.as_deref().map(Cow::Borrowed),
+            url: self.url.as_deref().map(Cow::Borrowed),
+            file: self.file.as_deref().map(Cow::Borrowed),
+            _priv: ()
+        }
+    }
+}
+
 /// Item icons
 #[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub enum Icon<'a> {
@@ -911,6 +1535,33 @@ pub enum Icon<'a> {
     FileType(Cow<'a, str>)
 }
 
+impl<'a> Icon<'a> {
+    /// Converts the `Icon` into one that owns its string.
+    ///
+    /// See `Item::into_owned()`.
+    pub fn into_owned(self) -> Icon<'static> {
+        match self {
+            Icon::Path(s) => Icon::Path(owned(s)),
+            Icon::File(s) => Icon::File(owned(s)),
+            Icon::FileType(s) => Icon::FileType(owned(s))
+        }
+    }
+
+    /// Returns a copy of the `Icon` that owns its string.
+    pub fn to_owned(&self) -> Icon<'static> {
+        self.as_borrowed().into_owned()
+    }
+
+    /// Returns a copy of the `Icon` that borrows its string from this one.
+    pub fn as_borrowed(&self) -> Icon<'_> {
+        match *self {
+            Icon::Path(ref s) => Icon::Path(Cow::Borrowed(s)),
+            Icon::File(ref s) => Icon::File(Cow::Borrowed(s)),
+            Icon::FileType(ref s) => Icon::FileType(Cow::Borrowed(s))
+        }
+    }
+}
+
 /// Item types
 #[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
 pub enum ItemType {
@@ -943,3 +1594,106 @@ fn test_variables() {
     let item = builder.into_item();
     assert_eq!(item.variables, HashMap::new());
 }
+
+#[test]
+fn test_from_path() {
+    let home = Path::new("/Users/lily");
+    let item = ItemBuilder::from_path_with_home(Path::new("/Users/lily/Documents/notes.txt"),
+                                                Some(home)).unwrap().into_item();
+    assert_eq!(item.title, "notes.txt");
+    assert_eq!(item.subtitle.as_deref(), Some("~/Documents"));
+    assert_eq!(item.arg.as_deref(), Some("/Users/lily/Documents/notes.txt"));
+    assert_eq!(item.uid, item.arg);
+    assert_eq!(item.quicklook_url, item.arg);
+    assert_eq!(item.icon, Some(Icon::File(Cow::Borrowed("/Users/lily/Documents/notes.txt"))));
+    assert_eq!(item.type_, ItemType::File);
+
+    let item = ItemBuilder::from_path_with_home(Path::new("/Users/lily/notes.txt"), Some(home))
+                           .unwrap().into_item();
+    assert_eq!(item.subtitle.as_deref(), Some("~"));
+    let item = ItemBuilder::from_path_with_home(Path::new("/Users/lilyb/x"), Some(home))
+                           .unwrap().into_item();
+    assert_eq!(item.subtitle.as_deref(), Some("/Users/lilyb"));
+    let item = ItemBuilder::from_path_with_home(Path::new("/"), None).unwrap().into_item();
+    assert_eq!((&*item.title, item.subtitle), ("/", None));
+    let item = ItemBuilder::from_path_skip_check("/tmp/x").unwrap().into_item();
+    assert_eq!(item.type_, ItemType::FileSkipCheck);
+
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(OsStr::from_bytes(b"/tmp/caf\xe9"));
+        assert_eq!(ItemBuilder::from_path(path).unwrap_err().path(), path);
+    }
+}
+
+#[test]
+fn test_from_url() {
+    let item = ItemBuilder::from_url("Rust", "https://user@www.rust-lang.org:443/learn?x#y")
+                           .unwrap().action_url("https://www.rust-lang.org/").into_item();
+    assert_eq!(item.subtitle.as_deref(), Some("www.rust-lang.org"));
+    assert_eq!(item.arg.as_deref(), Some("https://user@www.rust-lang.org:443/learn?x#y"));
+    assert_eq!((&item.uid, &item.quicklook_url), (&item.arg, &item.arg));
+    assert_eq!(item.text_copy, item.arg);
+    assert_eq!(item.to_json()["action"], json!({"url": "https://www.rust-lang.org/"}));
+
+    let host = |url| ItemBuilder::from_url("", url).map(|b| b.into_item().subtitle.unwrap());
+    assert_eq!(host("http://[::1]:8080/").unwrap(), "[::1]");
+    assert_eq!(host("mailto:lily@example.com").unwrap(), "mailto:lily@example.com");
+    assert_eq!(host("file:///tmp/x").unwrap(), "file:///tmp/x");
+    for url in &["", "example.com", "http:example.com", "https://", "1http://x", "http://a b",
+                 "mailto:"] {
+        assert_eq!(host(url).unwrap_err().url(), *url);
+    }
+}
+
+#[test]
+fn test_into_owned() {
+    let title = String::from("Title");
+    let path = String::from("/tmp/file.txt");
+    let item = ItemBuilder::new(&*title)
+                           .subtitle(title.clone())
+                           .arg(&*path)
+                           .icon_file(&*path)
+                           .action_file(&*path)
+                           .arg_mod(Modifier::Command, &*path)
+                           .icon_filetype_mod(Modifier::Command, &*path)
+                           .variable(&*title, &*path)
+                           .variable_mod(Modifier::Command, &*title, &*path)
+                           .into_item();
+    let copy = item.to_owned();
+    let borrowed = copy.as_borrowed();
+    assert_eq!(borrowed, item);
+    assert!(matches!(borrowed.arg, Some(Cow::Borrowed(_))));
+    let owned = item.into_owned();
+    drop((title, path));
+    let owned = ::std::thread::spawn(move || owned).join().unwrap();
+    assert_eq!(owned, copy);
+    let icon = &owned.modifiers[&Modifier::Command].icon;
+    assert!(matches!(*icon, Some(Icon::FileType(Cow::Owned(_)))));
+}
+
+#[test]
+fn test_modifier_builder() {
+    let data = ModifierBuilder::new()
+                              .subtitle("Subtitle")
+                              .arg("arg")
+                              .valid(false)
+                              .icon_filetype("public.folder")
+                              .variable("key", "value")
+                              .into_modifier_data();
+    let item = ItemBuilder::new("Item").modifier_data(Modifier::Option, data.clone()).into_item();
+    assert_eq!(item.modifiers[&Modifier::Option], data);
+    #[allow(deprecated)]
+    let old = ItemBuilder::new("Item")
+                          .modifier(Modifier::Option, Some("Subtitle"), Some("arg"), false,
+                                    Some(Icon::FileType("public.folder".into())))
+                          .variable_mod(Modifier::Option, "key", "value")
+                          .into_item();
+    assert_eq!(old, item);
+
+    let mut builder = ItemBuilder::new("Item").modifier_data(Modifier::Option, data);
+    builder.set_modifier_data(Modifier::Option, ModifierBuilder::new());
+    assert!(builder.into_item().modifiers.is_empty());
+}
@@ -0,0 +1,335 @@
+//! A `log` backend for workflows
+//!
+//! Alfred shows a script's stderr in the workflow debugger, but only while the debugger is open.
+//! Writing to stderr when it isn't open is wasted work, so the `Logger` in this module only logs to
+//! stderr when `alfred::env::is_debug()` is `true`. Each line is prefixed with a UTC timestamp and
+//! the workflow name.
+//!
+//! The logger can also write to a log file in the workflow cache directory, whether or not the
+//! debugger is open, so problems can be investigated after the fact. The file is rotated when it
+//! exceeds a maximum size.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! #[macro_use] extern crate log;
+//!
+//! fn main() {
+//!     alfred::logger::Builder::new()
+//!                             .level(log::LevelFilter::Debug)
+//!                             .log_file("workflow.log")
+//!                             .init()
+//!                             .unwrap();
+//!     info!("starting up");
+//! }
+//! ```
+
+use log::{self, Log, LevelFilter, Metadata, Record, SetLoggerError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use env::Environment;
+
+/// The default maximum size of the log file before it's rotated.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// The default number of rotated log files that are kept.
+pub const DEFAULT_MAX_ROTATED_FILES: usize = 2;
+
+/// Sets up a `Logger` with the default configuration and installs it as the global logger.
+///
+/// The default configuration logs messages at `Info` and above to stderr when the workflow
+/// debugger is open, and doesn't write a log file.
+pub fn init() -> Result<(), SetLoggerError> {
+    Builder::new().init()
+}
+
+/// Helper for configuring a `Logger`.
+#[derive(Clone,Debug)]
+pub struct Builder {
+    level: LevelFilter,
+    env: Environment,
+    file_name: Option<PathBuf>,
+    max_file_size: u64,
+    max_rotated_files: usize
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Returns a new `Builder` that reads the process environment.
+    pub fn new() -> Builder {
+        Builder {
+            level: LevelFilter::Info,
+            env: Environment::from_env(),
+            file_name: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_rotated_files: DEFAULT_MAX_ROTATED_FILES
+        }
+    }
+
+    /// Sets the maximum level of messages to log. The default is `Info`.
+    pub fn level(mut self, level: LevelFilter) -> Builder {
+        self.level = level;
+        self
+    }
+
+    /// Sets the environment used to find the debug flag, workflow name, and cache directory.
+    pub fn environment(mut self, env: Environment) -> Builder {
+        self.env = env;
+        self
+    }
+
+    /// Enables logging to a file with the given name in the workflow cache directory.
+    ///
+    /// If the workflow has no cache directory, no file is written.
+    pub fn log_file<P: Into<PathBuf>>(mut self, name: P) -> Builder {
+        self.file_name = Some(name.into());
+        self
+    }
+
+    /// Sets the size in bytes above which the log file is rotated.
+    pub fn max_file_size(mut self, bytes: u64) -> Builder {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Sets how many rotated log files are kept, as `<name>.1`, `<name>.2`, etc.
+    pub fn max_rotated_files(mut self, count: usize) -> Builder {
+        self.max_rotated_files = count;
+        self
+    }
+
+    /// Returns the configured `Logger`.
+    pub fn build(self) -> Logger {
+        let (max_size, max_rotated) = (self.max_file_size, self.max_rotated_files);
+        let path = match (self.file_name, self.env.workflow_cache()) {
+            (Some(name), Some(cache)) => Some(cache.join(name)),
+            _ => None
+        };
+        Logger {
+            level: self.level,
+            stderr: self.env.is_debug(),
+            name: self.env.workflow_name(),
+            file: path.map(|path| Mutex::new(LogFile {
+                path,
+                file: None,
+                size: 0,
+                max_size,
+                max_rotated
+            }))
+        }
+    }
+
+    /// Builds the `Logger` and installs it as the global logger.
+    ///
+    /// This also sets the global maximum log level.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let logger = self.build();
+        let level = if logger.is_active() { logger.level } else { LevelFilter::Off };
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+/// A `log::Log` implementation for workflows.
+///
+/// See the module documentation for details.
+#[derive(Debug)]
+pub struct Logger {
+    level: LevelFilter,
+    stderr: bool,
+    name: Option<String>,
+    file: Option<Mutex<LogFile>>
+}
+
+impl Logger {
+    /// Returns the path of the log file, if file logging is enabled.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.file.as_ref().map(|f| lock(f).path.clone())
+    }
+
+    fn is_active(&self) -> bool {
+        self.stderr || self.file.is_some()
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let mut line = timestamp(SystemTime::now());
+        if let Some(ref name) = self.name {
+            line.push_str(" [");
+            line.push_str(name);
+            line.push(']');
+        }
+        line.push_str(&format!(" {:<5} {}: {}\n", record.level(), record.target(), record.args()));
+        line
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.is_active() && metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = self.format(record);
+        if self.stderr {
+            let _ = io::stderr().write_all(line.as_bytes());
+        }
+        if let Some(ref file) = self.file {
+            // Logging must never take down the workflow, so file errors are ignored.
+            let _ = lock(file).write(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            if let Some(ref mut f) = lock(file).file {
+                let _ = f.flush();
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> ::std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    max_size: u64,
+    max_rotated: usize
+}
+
+impl LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.file = None;
+            rotate(&self.path, self.max_rotated)?;
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+            self.size = 0;
+        }
+        if let Some(ref mut file) = self.file {
+            file.write_all(buf)?;
+            self.size += buf.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Renames `path` to `path.1`, `path.1` to `path.2`, and so on, dropping the oldest file.
+fn rotate(path: &Path, max_rotated: usize) -> io::Result<()> {
+    let rotated = |n: usize| {
+        let mut s = path.as_os_str().to_owned();
+        s.push(format!(".{}", n));
+        PathBuf::from(s)
+    };
+    if max_rotated == 0 {
+        return fs::remove_file(path);
+    }
+    let _ = fs::remove_file(rotated(max_rotated));
+    for n in (1..max_rotated).rev() {
+        let from = rotated(n);
+        if from.exists() {
+            fs::rename(&from, rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+/// Formats `time` as an ISO 8601 UTC timestamp with millisecond precision.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Howard Hinnant's civil_from_days algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day,
+            rem / 3600, rem / 60 % 60, rem % 60, since_epoch.subsec_millis())
+}
+
+#[test]
+fn test_timestamp() {
+    use std::time::Duration;
+    assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_millis(951_782_400_123)),
+               "2000-02-29T00:00:00.123Z");
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_792_328_105)),
+               "2026-10-18T12:55:05.000Z");
+}
+
+#[test]
+fn test_logger() {
+    let dir = ::std::env::temp_dir().join(format!("alfred-logger-test-{}", ::std::process::id()));
+    let env = Environment::from_map(vec![
+        ("alfred_workflow_cache", dir.to_str().unwrap()),
+        ("alfred_workflow_name", "Example"),
+    ]);
+    let logger = Builder::new().environment(env.clone()).build();
+    assert!(!logger.enabled(&Metadata::builder().level(log::Level::Error).build()));
+
+    let logger = Builder::new().environment(env)
+                               .level(LevelFilter::Debug)
+                               .log_file("test.log")
+                               .max_file_size(200)
+                               .max_rotated_files(1)
+                               .build();
+    let path = logger.log_path().unwrap();
+    assert_eq!(path, dir.join("test.log"));
+    assert!(!logger.enabled(&Metadata::builder().level(log::Level::Trace).build()));
+
+    let log = |msg: &str| {
+        logger.log(&Record::builder().level(log::Level::Info)
+                                     .target("test")
+                                     .args(format_args!("{}", msg))
+                                     .build());
+    };
+    log("first");
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(contents.ends_with(" [Example] INFO  test: first\n"), "{:?}", contents);
+
+    for _ in 0..4 {
+        log("filling up the log file");
+    }
+    log("last");
+    let mut rotated = path.clone().into_os_string();
+    rotated.push(".1");
+    assert!(Path::new(&rotated).exists());
+    assert!(!dir.join("test.log.2").exists());
+    assert!(fs::read_to_string(&path).unwrap().ends_with("test: last\n"));
+    assert!(fs::metadata(&path).unwrap().len() <= 200);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,657 @@
+//! Packaging workflows as `.alfredworkflow` files
+//!
+//! An `.alfredworkflow` file is a zip archive of a workflow directory, with `info.plist` at its
+//! root. `Packager` builds one from a directory, checking that `info.plist` is valid and blanking
+//! the values of any variables listed under "Don't Export" (`variablesdontexport`) so they aren't
+//! leaked. `inspect()` and `unpack()` read an existing archive.
+//!
+//! Files can be left out with ignore patterns, given with `Packager::ignore()` or listed one per
+//! line in an `.alfredignore` file in the workflow directory. A pattern matches a file or
+//! directory name anywhere in the tree, or, if it contains a `/`, a path relative to the workflow
+//! directory. `*` matches any sequence of characters other than `/`, `?` matches a single
+//! character, and a trailing `/` matches only directories. Lines in `.alfredignore` starting with
+//! `#` are comments. The patterns in `DEFAULT_IGNORE` are always applied.
+//!
+//! This module requires the `package` feature, which also builds the `alfred-package` binary.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::package::Packager;
+//!
+//! let packager = Packager::new("workflow").ignore("*.md");
+//! let file_name = packager.file_name().unwrap();
+//! packager.write_to(&file_name).unwrap();
+//! # }
+//! ```
+
+use plist::{self, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Patterns that are never packaged.
+///
+/// `prefs.plist` holds the user's values for the workflow's configuration and is excluded by
+/// Alfred's own export.
+pub const DEFAULT_IGNORE: &[&str] = &[".git/", ".DS_Store", "*.alfredworkflow", ".alfredignore",
+                                     "/prefs.plist"];
+
+/// The name of the file that ignore patterns are read from.
+pub const IGNORE_FILE: &str = ".alfredignore";
+
+/// An error packaging, unpacking, or inspecting a workflow.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// The archive couldn't be read or written.
+    Zip(ZipError),
+    /// `info.plist` couldn't be parsed.
+    Plist(plist::Error),
+    /// The workflow isn't valid. The message describes the problem.
+    Invalid(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::Zip(ref err) => write!(f, "invalid archive: {}", err),
+            Error::Plist(ref err) => write!(f, "couldn't read info.plist: {}", err),
+            Error::Invalid(ref msg) => write!(f, "invalid workflow: {}", msg)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Zip(ref err) => Some(err),
+            Error::Plist(ref err) => Some(err),
+            Error::Invalid(_) => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(err: ZipError) -> Error {
+        Error::Zip(err)
+    }
+}
+
+impl From<plist::Error> for Error {
+    fn from(err: plist::Error) -> Error {
+        Error::Plist(err)
+    }
+}
+
+fn invalid<T, S: Into<String>>(msg: S) -> Result<T, Error> {
+    Err(Error::Invalid(msg.into()))
+}
+
+/// A summary of a packaged workflow.
+///
+/// This is returned by `inspect()`, `unpack()`, and `Packager::info()`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct WorkflowInfo {
+    name: String,
+    bundle_id: Option<String>,
+    version: Option<String>,
+    variables: BTreeMap<String, String>,
+    dont_export: Vec<String>,
+    files: Vec<String>
+}
+
+impl WorkflowInfo {
+    fn new(plist: &Value, files: Vec<String>) -> WorkflowInfo {
+        let dict = plist.as_dictionary();
+        let string = |key: &str| dict.and_then(|d| d.get(key))
+                                     .and_then(Value::as_string)
+                                     .filter(|s| !s.is_empty())
+                                     .map(str::to_owned);
+        WorkflowInfo {
+            name: string("name").unwrap_or_default(),
+            bundle_id: string("bundleid"),
+            version: string("version"),
+            variables: dict.and_then(|d| d.get("variables"))
+                           .and_then(Value::as_dictionary)
+                           .map(|vars| vars.iter().filter_map(|(k, v)| {
+                               v.as_string().map(|v| (k.clone(), v.to_owned()))
+                           }).collect())
+                           .unwrap_or_default(),
+            dont_export: dont_export(plist),
+            files
+        }
+    }
+
+    /// Returns the workflow name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the workflow bundle identifier, if it has one.
+    pub fn bundle_id(&self) -> Option<&str> {
+        self.bundle_id.as_deref()
+    }
+
+    /// Returns the workflow version, if it has one.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the workflow's environment variables, as they appear in the archive.
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    /// Returns the names of the variables whose values aren't exported.
+    pub fn dont_export(&self) -> &[String] {
+        &self.dont_export
+    }
+
+    /// Returns the paths of the files in the archive, sorted, using `/` as the separator.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+}
+
+/// Builds an `.alfredworkflow` archive from a workflow directory.
+#[derive(Clone,Debug)]
+pub struct Packager {
+    dir: PathBuf,
+    ignore: Vec<String>,
+    use_ignore_file: bool
+}
+
+impl Packager {
+    /// Returns a new `Packager` for the workflow in `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Packager {
+        Packager {
+            dir: dir.into(),
+            ignore: Vec::new(),
+            use_ignore_file: true
+        }
+    }
+
+    /// Adds a pattern for files that shouldn't be packaged.
+    ///
+    /// See the module documentation for the pattern syntax.
+    pub fn ignore<S: Into<String>>(mut self, pattern: S) -> Packager {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Sets whether patterns are read from an `.alfredignore` file in the workflow directory.
+    /// The default is `true`.
+    pub fn use_ignore_file(mut self, flag: bool) -> Packager {
+        self.use_ignore_file = flag;
+        self
+    }
+
+    /// Returns the paths of the files that will be packaged, sorted, using `/` as the separator.
+    pub fn files(&self) -> Result<Vec<String>, Error> {
+        let patterns = self.patterns()?;
+        let mut files = Vec::new();
+        walk(&self.dir, "", &patterns, &mut files)?;
+        files.sort();
+        if !files.iter().any(|f| f == "info.plist") {
+            return invalid("info.plist is missing");
+        }
+        Ok(files)
+    }
+
+    /// Reads and validates `info.plist`, and returns a summary of the workflow as it will be
+    /// packaged.
+    pub fn info(&self) -> Result<WorkflowInfo, Error> {
+        let files = self.files()?;
+        Ok(WorkflowInfo::new(&self.info_plist()?, files))
+    }
+
+    /// Returns the default file name for the archive, based on the workflow name.
+    pub fn file_name(&self) -> Result<String, Error> {
+        let plist = self.info_plist()?;
+        let name = WorkflowInfo::new(&plist, Vec::new()).name;
+        Ok(format!("{}.alfredworkflow", name.replace(['/', ':'], "-")))
+    }
+
+    /// Writes the archive to `w`, and returns `w`.
+    pub fn write<W: Write + Seek>(&self, w: W) -> Result<W, Error> {
+        let files = self.files()?;
+        let plist = self.info_plist()?;
+        let mut zip = ZipWriter::new(w);
+        for name in files {
+            let path = self.dir.join(&name);
+            let options = SimpleFileOptions::default()
+                              .compression_method(CompressionMethod::Deflated)
+                              .unix_permissions(mode(&fs::metadata(&path)?));
+            zip.start_file(name.as_str(), options)?;
+            if name == "info.plist" {
+                plist.to_writer_xml(&mut zip)?;
+            } else {
+                io::copy(&mut File::open(&path)?, &mut zip)?;
+            }
+        }
+        Ok(zip.finish()?)
+    }
+
+    /// Writes the archive to a file at `path`, replacing it if it exists.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        // Check the workflow before creating the file, so an invalid workflow leaves nothing
+        // behind.
+        self.files()?;
+        self.info_plist()?;
+        let file = File::create(path)?;
+        self.write(file)?.sync_all()?;
+        Ok(())
+    }
+
+    /// Returns the validated `info.plist` with the "Don't Export" variables blanked out.
+    fn info_plist(&self) -> Result<Value, Error> {
+        let path = self.dir.join("info.plist");
+        if !path.is_file() {
+            return invalid("info.plist is missing");
+        }
+        let mut plist = Value::from_file(path)?;
+        validate(&plist)?;
+        blank_dont_export(&mut plist);
+        Ok(plist)
+    }
+
+    fn patterns(&self) -> Result<Vec<Pattern>, Error> {
+        let mut patterns: Vec<Pattern> = DEFAULT_IGNORE.iter().map(|p| Pattern::new(p)).collect();
+        patterns.extend(self.ignore.iter().map(|p| Pattern::new(p)));
+        if self.use_ignore_file {
+            match fs::read_to_string(self.dir.join(IGNORE_FILE)) {
+                Ok(contents) => {
+                    patterns.extend(contents.lines()
+                                            .map(str::trim)
+                                            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                                            .map(Pattern::new));
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into())
+            }
+        }
+        Ok(patterns)
+    }
+}
+
+/// Reads the archive in `r` and returns a summary of the workflow.
+///
+/// This fails if the archive has no valid `info.plist` at its root.
+pub fn inspect<R: Read + Seek>(r: R) -> Result<WorkflowInfo, Error> {
+    let mut zip = ZipArchive::new(r)?;
+    inspect_archive(&mut zip)
+}
+
+/// Extracts the archive in `r` into `dir`, and returns a summary of the workflow.
+///
+/// The archive is checked first, and nothing is extracted if it isn't a valid workflow. Entries
+/// with paths outside of `dir` are rejected.
+pub fn unpack<R: Read + Seek, P: AsRef<Path>>(r: R, dir: P) -> Result<WorkflowInfo, Error> {
+    let mut zip = ZipArchive::new(r)?;
+    let info = inspect_archive(&mut zip)?;
+    for i in 0..zip.len() {
+        let file = zip.by_index(i)?;
+        if file.enclosed_name().is_none() {
+            return invalid(format!("`{}` is outside of the workflow directory", file.name()));
+        }
+    }
+    zip.extract(dir)?;
+    Ok(info)
+}
+
+fn inspect_archive<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<WorkflowInfo, Error> {
+    let mut files: Vec<String> = Vec::new();
+    for i in 0..zip.len() {
+        let file = zip.by_index(i)?;
+        if !file.is_dir() {
+            files.push(file.name().to_owned());
+        }
+    }
+    files.sort();
+    let mut data = Vec::new();
+    match zip.by_name("info.plist") {
+        Ok(mut file) => { file.read_to_end(&mut data)?; }
+        Err(ZipError::FileNotFound) => return invalid("info.plist is missing"),
+        Err(err) => return Err(err.into())
+    }
+    let plist = Value::from_reader(Cursor::new(data))?;
+    validate(&plist)?;
+    Ok(WorkflowInfo::new(&plist, files))
+}
+
+/// Checks that `plist` has the structure Alfred expects of `info.plist`.
+fn validate(plist: &Value) -> Result<(), Error> {
+    let dict = match plist.as_dictionary() {
+        Some(dict) => dict,
+        None => return invalid("info.plist isn't a dictionary")
+    };
+    match dict.get("name").and_then(Value::as_string) {
+        Some(name) if !name.trim().is_empty() => {}
+        _ => return invalid("info.plist has no `name`")
+    }
+    for key in &["bundleid", "version", "createdby", "description", "readme"] {
+        if dict.get(key).is_some_and(|v| v.as_string().is_none()) {
+            return invalid(format!("`{}` isn't a string", key));
+        }
+    }
+
+    let mut uids = HashSet::new();
+    if let Some(objects) = dict.get("objects") {
+        let objects = match objects.as_array() {
+            Some(objects) => objects,
+            None => return invalid("`objects` isn't an array")
+        };
+        for object in objects {
+            let object = match object.as_dictionary() {
+                Some(object) => object,
+                None => return invalid("an object isn't a dictionary")
+            };
+            let uid = match object.get("uid").and_then(Value::as_string) {
+                Some(uid) => uid,
+                None => return invalid("an object has no `uid`")
+            };
+            if object.get("type").and_then(Value::as_string).is_none() {
+                return invalid(format!("object {} has no `type`", uid));
+            }
+            if !uids.insert(uid) {
+                return invalid(format!("more than one object has uid {}", uid));
+            }
+        }
+    }
+
+    if let Some(connections) = dict.get("connections") {
+        let connections = match connections.as_dictionary() {
+            Some(connections) => connections,
+            None => return invalid("`connections` isn't a dictionary")
+        };
+        for (source, targets) in connections {
+            if !uids.contains(source.as_str()) {
+                return invalid(format!("connection from unknown object {}", source));
+            }
+            let targets = match targets.as_array() {
+                Some(targets) => targets,
+                None => return invalid(format!("connections from {} aren't an array", source))
+            };
+            for target in targets {
+                match target.as_dictionary()
+                            .and_then(|t| t.get("destinationuid"))
+                            .and_then(Value::as_string) {
+                    Some(uid) if uids.contains(uid) => {}
+                    Some(uid) => {
+                        return invalid(format!("connection from {} to unknown object {}",
+                                               source, uid));
+                    }
+                    None => {
+                        return invalid(format!("connection from {} has no `destinationuid`",
+                                               source));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(variables) = dict.get("variables") {
+        match variables.as_dictionary() {
+            Some(vars) if vars.values().all(|v| v.as_string().is_some()) => {}
+            _ => return invalid("`variables` isn't a dictionary of strings")
+        }
+    }
+    if let Some(names) = dict.get("variablesdontexport") {
+        match names.as_array() {
+            Some(names) if names.iter().all(|v| v.as_string().is_some()) => {}
+            _ => return invalid("`variablesdontexport` isn't an array of strings")
+        }
+    }
+    Ok(())
+}
+
+fn dont_export(plist: &Value) -> Vec<String> {
+    plist.as_dictionary()
+         .and_then(|d| d.get("variablesdontexport"))
+         .and_then(Value::as_array)
+         .map(|names| names.iter().filter_map(Value::as_string).map(str::to_owned).collect())
+         .unwrap_or_default()
+}
+
+/// Replaces the values of the "Don't Export" variables with empty strings, as Alfred does.
+fn blank_dont_export(plist: &mut Value) {
+    let names = dont_export(plist);
+    let vars = plist.as_dictionary_mut()
+                    .and_then(|d| d.get_mut("variables"))
+                    .and_then(Value::as_dictionary_mut);
+    if let Some(vars) = vars {
+        for name in &names {
+            if let Some(value) = vars.get_mut(name) {
+                *value = Value::String(String::new());
+            }
+        }
+    }
+}
+
+fn walk(dir: &Path, prefix: &str, patterns: &[Pattern], files: &mut Vec<String>)
+       -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(name) => {
+                return invalid(format!("file name {:?} isn't valid UTF-8", name));
+            }
+        };
+        let path = format!("{}{}", prefix, name);
+        // Follow symlinks, so linked files are packaged by value.
+        let is_dir = fs::metadata(entry.path())?.is_dir();
+        if patterns.iter().any(|p| p.matches(&path, &name, is_dir)) {
+            continue;
+        }
+        if is_dir {
+            walk(&entry.path(), &format!("{}/", path), patterns, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn mode(_: &fs::Metadata) -> u32 {
+    0o644
+}
+
+/// An ignore pattern. See the module documentation for the syntax.
+#[derive(Clone,Debug)]
+struct Pattern {
+    glob: String,
+    anchored: bool,
+    dir_only: bool
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Pattern {
+        let dir_only = pattern.ends_with('/');
+        let glob = pattern.trim_end_matches('/');
+        let anchored = glob.contains('/');
+        Pattern {
+            glob: glob.trim_start_matches('/').to_owned(),
+            anchored,
+            dir_only
+        }
+    }
+
+    /// Returns whether the pattern matches the file with the given relative path and name.
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let text = if self.anchored { path } else { name };
+        glob_match(self.glob.as_bytes(), text.as_bytes())
+    }
+}
+
+fn glob_match(glob: &[u8], text: &[u8]) -> bool {
+    match glob.split_first() {
+        None => text.is_empty(),
+        Some((&b'*', rest)) => {
+            // `*` can match an empty string, or any character but `/` followed by more.
+            glob_match(rest, text) || match text.split_first() {
+                Some((&c, text)) if c != b'/' => glob_match(glob, text),
+                _ => false
+            }
+        }
+        Some((&g, rest)) => match text.split_first() {
+            Some((&c, text)) if c == g || (g == b'?' && c != b'/') => glob_match(rest, text),
+            _ => false
+        }
+    }
+}
+
+#[test]
+fn test_patterns() {
+    let matches = |pattern: &str, path: &str, is_dir: bool| {
+        let name = path.rsplit('/').next().unwrap();
+        Pattern::new(pattern).matches(path, name, is_dir)
+    };
+    assert!(matches(".DS_Store", "icons/.DS_Store", false));
+    assert!(matches("*.md", "README.md", false));
+    assert!(matches("*.md", "docs/usage.md", false));
+    assert!(!matches("*.md", "README.mdx", false));
+    assert!(matches("icon?.png", "icon1.png", false));
+    assert!(matches("/prefs.plist", "prefs.plist", false));
+    assert!(!matches("/prefs.plist", "nested/prefs.plist", false));
+    assert!(matches("src/*.rs", "src/main.rs", false));
+    assert!(!matches("src/*.rs", "src/bin/main.rs", false));
+    assert!(matches("target/", "target", true));
+    assert!(!matches("target/", "target", false));
+}
+
+#[cfg(test)]
+const TEST_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>bundleid</key>
+    <string>com.example.test</string>
+    <key>connections</key>
+    <dict>
+        <key>A</key>
+        <array>
+            <dict>
+                <key>destinationuid</key>
+                <string>B</string>
+            </dict>
+        </array>
+    </dict>
+    <key>name</key>
+    <string>Test: Workflow</string>
+    <key>objects</key>
+    <array>
+        <dict>
+            <key>type</key>
+            <string>alfred.workflow.input.scriptfilter</string>
+            <key>uid</key>
+            <string>A</string>
+        </dict>
+        <dict>
+            <key>type</key>
+            <string>alfred.workflow.action.openurl</string>
+            <key>uid</key>
+            <string>B</string>
+        </dict>
+    </array>
+    <key>variables</key>
+    <dict>
+        <key>API_TOKEN</key>
+        <string>secret</string>
+        <key>REGION</key>
+        <string>eu</string>
+    </dict>
+    <key>variablesdontexport</key>
+    <array>
+        <string>API_TOKEN</string>
+    </array>
+    <key>version</key>
+    <string>1.2.0</string>
+</dict>
+</plist>
+"#;
+
+#[test]
+fn test_package() {
+    let dir = ::std::env::temp_dir().join(format!("alfred-package-test-{}", ::std::process::id()));
+    let workflow = dir.join("workflow");
+    fs::create_dir_all(workflow.join(".git")).unwrap();
+    fs::create_dir_all(workflow.join("icons")).unwrap();
+    fs::write(workflow.join("info.plist"), TEST_PLIST).unwrap();
+    fs::write(workflow.join("main"), "#!/bin/sh\n").unwrap();
+    fs::write(workflow.join("icons/icon.png"), "png").unwrap();
+    fs::write(workflow.join("icons/.DS_Store"), "").unwrap();
+    fs::write(workflow.join(".git/config"), "").unwrap();
+    fs::write(workflow.join("prefs.plist"), "").unwrap();
+    fs::write(workflow.join("notes.txt"), "").unwrap();
+    fs::write(workflow.join(IGNORE_FILE), "# comment\n*.txt\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(workflow.join("main"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let packager = Packager::new(&workflow);
+    assert_eq!(packager.file_name().unwrap(), "Test- Workflow.alfredworkflow");
+    let archive = packager.write(Cursor::new(Vec::new())).unwrap().into_inner();
+
+    let info = inspect(Cursor::new(&archive)).unwrap();
+    assert_eq!(info.name(), "Test: Workflow");
+    assert_eq!(info.bundle_id(), Some("com.example.test"));
+    assert_eq!(info.version(), Some("1.2.0"));
+    assert_eq!(info.files(), &["icons/icon.png", "info.plist", "main"]);
+    assert_eq!(info.dont_export(), &["API_TOKEN"]);
+    assert_eq!(info.variables()["API_TOKEN"], "");
+    assert_eq!(info.variables()["REGION"], "eu");
+    assert_eq!(packager.info().unwrap(), info);
+
+    let unpacked = dir.join("unpacked");
+    assert_eq!(unpack(Cursor::new(&archive), &unpacked).unwrap(), info);
+    assert_eq!(fs::read_to_string(unpacked.join("icons/icon.png")).unwrap(), "png");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(unpacked.join("main")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    let plist = TEST_PLIST.replace("<string>B</string>\n            </dict>",
+                                   "<string>C</string>\n            </dict>");
+    fs::write(workflow.join("info.plist"), plist).unwrap();
+    match packager.write(Cursor::new(Vec::new())) {
+        Err(Error::Invalid(msg)) => assert_eq!(msg, "connection from A to unknown object C"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ()))
+    }
+    fs::remove_file(workflow.join("info.plist")).unwrap();
+    assert!(matches!(packager.files(), Err(Error::Invalid(_))));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,195 @@
+//! Splitting script filter results into pages
+//!
+//! Alfred slows down when a script filter returns thousands of items, and results from web APIs
+//! often come in pages anyway. A `Paginator` shows one page at a time. The last item of each page
+//! is a "Next page…" item that isn't valid, so actioning it autocompletes the query with a page
+//! token appended. The script filter then runs again with the new query, and `parse()` separates
+//! the token from the rest of the query.
+//!
+//! The token is the last word of the query, prefixed with a marker (`page:` by default). For
+//! lists that are built in full, the token is the page number, and `page()` does all the work.
+//! For paged APIs, the token can be any string, such as a cursor returned by the API, and
+//! `next_item()` builds the "Next page…" item for it.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::pagination::Paginator;
+//!
+//! let paginator = Paginator::new(2);
+//! let items = || (1..6).map(|i| alfred::Item::new(format!("Item {}", i))).collect::<Vec<_>>();
+//!
+//! let page = paginator.page(items(), "foo");
+//! assert_eq!(page.len(), 3);
+//! let next_query = page[2].autocomplete.clone().unwrap();
+//! assert_eq!(next_query, "foo page:2");
+//!
+//! let page = paginator.page(items(), &next_query);
+//! assert_eq!(page[0].title, "Item 3");
+//! # }
+//! ```
+
+use urlscheme::percent_decode;
+use {Item, ItemBuilder};
+
+/// The default marker that precedes page tokens.
+pub const DEFAULT_MARKER: &str = "page:";
+
+/// A query split into the user's text and the page token.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Query<'q> {
+    /// The query without the page token.
+    pub text: &'q str,
+    /// The page token, if the query has one.
+    pub token: Option<String>
+}
+
+/// Splits results into pages and builds the items that move between them.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Paginator {
+    page_size: usize,
+    marker: String,
+    title: String
+}
+
+impl Paginator {
+    /// Returns a new `Paginator` that shows `page_size` items per page.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `page_size` is 0.
+    pub fn new(page_size: usize) -> Paginator {
+        assert!(page_size > 0, "page size must be greater than 0");
+        Paginator {
+            page_size,
+            marker: DEFAULT_MARKER.to_owned(),
+            title: "Next page…".to_owned()
+        }
+    }
+
+    /// Sets the marker that precedes page tokens in the query. The default is `page:`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `marker` is empty or contains whitespace.
+    pub fn marker<S: Into<String>>(mut self, marker: S) -> Paginator {
+        let marker = marker.into();
+        assert!(!marker.is_empty() && !marker.contains(char::is_whitespace),
+                "marker must be non-empty and contain no whitespace");
+        self.marker = marker;
+        self
+    }
+
+    /// Sets the title of the "Next page…" item.
+    pub fn title<S: Into<String>>(mut self, title: S) -> Paginator {
+        self.title = title.into();
+        self
+    }
+
+    /// Returns the number of items per page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Splits `query` into the user's text and the page token.
+    pub fn parse<'q>(&self, query: &'q str) -> Query<'q> {
+        let query = query.trim_end();
+        let start = query.rfind(char::is_whitespace).map_or(0, |i| {
+            i + query[i..].chars().next().map_or(0, char::len_utf8)
+        });
+        match query[start..].strip_prefix(&*self.marker).and_then(percent_decode) {
+            Some(token) => Query { text: query[..start].trim_end(), token: Some(token) },
+            None => Query { text: query, token: None }
+        }
+    }
+
+    /// Returns `text` with the page token appended, as parsed by `parse()`.
+    pub fn format_query(&self, text: &str, token: &str) -> String {
+        let text = text.trim_end();
+        let separator = if text.is_empty() { "" } else { " " };
+        format!("{}{}{}{}", text, separator, self.marker, encode(token))
+    }
+
+    /// Returns a "Next page…" item that autocompletes `text` with `token` appended.
+    ///
+    /// `text` is the query without a page token, i.e. `Query::text`.
+    pub fn next_item(&self, text: &str, token: &str) -> Item<'static> {
+        ItemBuilder::new(self.title.clone())
+                    .valid(false)
+                    .autocomplete(self.format_query(text, token))
+                    .into_item()
+    }
+
+    /// Returns the page of `items` selected by the page number in `query`.
+    ///
+    /// Pages are numbered from 1, and a query without a page number shows the first page. If
+    /// there are more items, a "Next page…" item is appended, with a subtitle showing which
+    /// items are shown.
+    pub fn page<'a>(&self, mut items: Vec<Item<'a>>, query: &str) -> Vec<Item<'a>> {
+        let query = self.parse(query);
+        let page = query.token.and_then(|t| t.parse::<usize>().ok()).unwrap_or(1).max(1);
+        let total = items.len();
+        let start = (page - 1).saturating_mul(self.page_size).min(total);
+        let end = start.saturating_add(self.page_size).min(total);
+        items.truncate(end);
+        items.drain(..start);
+        if end < total {
+            let mut next = self.next_item(query.text, &(page + 1).to_string());
+            next.subtitle = Some(format!("Showing {}–{} of {}", start + 1, end, total).into());
+            items.push(next);
+        }
+        items
+    }
+}
+
+/// Escapes whitespace and `%` in `token` so it stays a single word of the query.
+fn encode(token: &str) -> String {
+    let mut s = String::with_capacity(token.len());
+    for c in token.chars() {
+        if c == '%' || c.is_whitespace() {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                s.push_str(&format!("%{:02X}", b));
+            }
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+#[test]
+fn test_round_trip() {
+    let paginator = Paginator::new(10);
+    for &(text, token) in &[("", "2"), ("foo", "2"), ("foo  bar", "abc"), ("foo", "a b%c\tü"),
+                            ("page:3", "4"), ("ünïcode", "")] {
+        let query = paginator.format_query(text, token);
+        assert_eq!(paginator.parse(&query), Query { text, token: Some(token.to_owned()) },
+                   "{:?}", query);
+    }
+    assert_eq!(paginator.parse("foo bar "), Query { text: "foo bar", token: None });
+    assert_eq!(paginator.parse("foo page:%zz"), Query { text: "foo page:%zz", token: None });
+    let custom = Paginator::new(10).marker("→");
+    assert_eq!(custom.parse(&custom.format_query("foo", "x")),
+               Query { text: "foo", token: Some("x".to_owned()) });
+}
+
+#[test]
+fn test_page() {
+    let paginator = Paginator::new(2).title("More");
+    let items = || (1..6).map(|i| Item::new(format!("{}", i))).collect::<Vec<_>>();
+    let titles = |items: &[Item]| items.iter().map(|i| i.title.to_string()).collect::<Vec<_>>();
+
+    let page = paginator.page(items(), "q");
+    assert_eq!(titles(&page), ["1", "2", "More"]);
+    assert_eq!(page[2].subtitle.as_deref(), Some("Showing 1–2 of 5"));
+    assert!(!page[2].valid);
+    let page = paginator.page(items(), page[2].autocomplete.as_ref().unwrap());
+    assert_eq!(titles(&page), ["3", "4", "More"]);
+    assert_eq!(page[2].autocomplete.as_deref(), Some("q page:3"));
+    assert_eq!(titles(&paginator.page(items(), "q page:3")), ["5"]);
+    assert!(paginator.page(items(), "q page:9").is_empty());
+    assert_eq!(titles(&paginator.page(items(), "q page:x")), ["1", "2", "More"]);
+}
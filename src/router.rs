@@ -0,0 +1,330 @@
+//! Dispatching a script filter's query to subcommands
+//!
+//! Larger workflows often use a single keyword with subcommands, such as `gh pr list` and
+//! `gh issue view 42`. A `Router` holds a tree of subcommands, splits the query (the text after
+//! the keyword) on whitespace, and walks the tree with the leading words:
+//!
+//! * If the words name a command, its handler is called with the remaining words as arguments.
+//! * If the query ends partway through a subcommand name, the result is a completion item for each
+//!   subcommand starting with what was typed. Completion items are invalid and autocomplete to
+//!   the subcommand's path followed by a space, so pressing Return or Tab fills in the subcommand
+//!   and the script filter runs again.
+//! * If the query stops at a group of subcommands that has no handler of its own, the result is a
+//!   completion item for each subcommand in the group. An empty query lists the top-level
+//!   subcommands, unless a handler is registered for the empty path.
+//! * If a word doesn't match any subcommand of a group without a handler, the result is a single
+//!   invalid item saying so.
+//!
+//! Subcommand names are matched case-insensitively. A command with subcommands of its own gets
+//! the query only when the next word isn't (the start of) one of those subcommands.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::router::Router;
+//! use alfred::Item;
+//!
+//! let router = Router::new()
+//!                     .group("pr", "Pull requests")
+//!                     .command("pr list", "List open pull requests", |_| {
+//!                         vec![Item::new("#1 Fix the build")]
+//!                     })
+//!                     .command("pr view", "View a pull request", |args| {
+//!                         vec![Item::new(format!("Pull request #{}", args.rest()))]
+//!                     })
+//!                     .command("issue list", "List open issues", |_| vec![]);
+//!
+//! let items = router.route("p");
+//! assert_eq!(items[0].title, "pr");
+//! assert_eq!(items[0].autocomplete.as_deref(), Some("pr "));
+//!
+//! let items = router.route("pr ");
+//! assert_eq!(items[0].title, "list");
+//! assert_eq!(items[1].autocomplete.as_deref(), Some("pr view "));
+//!
+//! let items = router.route("pr view 42");
+//! assert_eq!(items[0].title, "Pull request #42");
+//! # }
+//! ```
+
+use std::fmt;
+
+use collection::Items;
+use {Item, ItemBuilder};
+
+type Handler<'a> = Box<dyn Fn(&Args) -> Vec<Item<'a>> + 'a>;
+
+/// Routes queries to subcommand handlers.
+///
+/// See the module documentation for details.
+pub struct Router<'a> {
+    root: Node<'a>
+}
+
+struct Node<'a> {
+    name: String,
+    description: Option<String>,
+    handler: Option<Handler<'a>>,
+    children: Vec<Node<'a>>
+}
+
+impl<'a> Node<'a> {
+    fn new(name: &str) -> Node<'a> {
+        Node {
+            name: name.to_owned(),
+            description: None,
+            handler: None,
+            children: Vec::new()
+        }
+    }
+
+    /// Returns the node for `path`, creating it and its parents as needed.
+    fn get_or_insert(&mut self, path: &str) -> &mut Node<'a> {
+        path.split_whitespace().fold(self, |node, name| {
+            let index = match node.children.iter().position(|c| c.name.eq_ignore_ascii_case(name)) {
+                Some(index) => index,
+                None => {
+                    node.children.push(Node::new(name));
+                    node.children.len() - 1
+                }
+            };
+            &mut node.children[index]
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Node<'a>> {
+        self.children.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    fn completions<'b>(&'b self, prefix: &'b str) -> impl Iterator<Item=&'b Node<'a>> {
+        self.children.iter().filter(move |c| starts_with_ignore_case(&c.name, prefix))
+    }
+}
+
+impl<'a> fmt::Debug for Node<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+         .field("name", &self.name)
+         .field("description", &self.description)
+         .field("handler", &self.handler.is_some())
+         .field("children", &self.children)
+         .finish()
+    }
+}
+
+impl<'a> fmt::Debug for Router<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Router").field("root", &self.root).finish()
+    }
+}
+
+impl<'a> Default for Router<'a> {
+    fn default() -> Router<'a> {
+        Router::new()
+    }
+}
+
+impl<'a> Router<'a> {
+    /// Returns a new `Router` with no subcommands.
+    pub fn new() -> Router<'a> {
+        Router { root: Node::new("") }
+    }
+
+    /// Adds a command.
+    ///
+    /// `path` is the words that select the command, separated by spaces, such as `"pr list"`.
+    /// Groups along the path are created as needed. The empty path registers a handler for
+    /// queries that don't start with a subcommand. The description is used as the subtitle of the
+    /// command's completion item.
+    ///
+    /// Adding a command with the same path as an existing one replaces its handler and
+    /// description.
+    pub fn command<S, F>(mut self, path: &str, description: S, handler: F) -> Router<'a>
+        where S: Into<String>,
+              F: Fn(&Args) -> Vec<Item<'a>> + 'a
+    {
+        let node = self.root.get_or_insert(path);
+        node.description = Some(description.into());
+        node.handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the description of a group of subcommands, creating it if needed.
+    ///
+    /// Groups are created implicitly by `command()`, but have no subtitle unless described here.
+    pub fn group<S: Into<String>>(mut self, path: &str, description: S) -> Router<'a> {
+        self.root.get_or_insert(path).description = Some(description.into());
+        self
+    }
+
+    /// Routes `query` to a handler, or returns completion items for it.
+    ///
+    /// See the module documentation for details.
+    pub fn route(&self, query: &str) -> Items<'a> {
+        let words = query.split_whitespace().collect::<Vec<_>>();
+        // The last word is still being typed unless it's followed by whitespace.
+        let complete = if query.ends_with(char::is_whitespace) { words.len() } else {
+            words.len().saturating_sub(1)
+        };
+        let mut node = &self.root;
+        let mut depth = 0;
+        while depth < words.len() {
+            let word = words[depth];
+            if depth == complete {
+                let mut matches = node.completions(word).peekable();
+                if let Some(first) = matches.next() {
+                    let exact = first.name.eq_ignore_ascii_case(word) && matches.peek().is_none();
+                    if !exact {
+                        let path = &words[..depth];
+                        return Some(first).into_iter().chain(matches)
+                                          .map(|c| completion_item(path, c)).collect();
+                    }
+                }
+            }
+            match node.child(word) {
+                Some(child) => node = child,
+                None => break
+            }
+            depth += 1;
+        }
+
+        let path = &words[..depth];
+        match node.handler {
+            Some(ref handler) => {
+                let rest = rest(query, depth);
+                handler(&Args { path, args: &words[depth..], rest }).into()
+            }
+            None if depth < words.len() => {
+                let mut autocomplete = path.join(" ");
+                if !autocomplete.is_empty() {
+                    autocomplete.push(' ');
+                }
+                let names = node.children.iter().map(|c| &*c.name).collect::<Vec<_>>();
+                let item = ItemBuilder::new(format!("Unknown command “{}”", words[depth]))
+                                       .subtitle(format!("Commands: {}", names.join(", ")))
+                                       .autocomplete(autocomplete)
+                                       .valid(false)
+                                       .into_item();
+                vec![item].into()
+            }
+            None => node.children.iter().map(|c| completion_item(path, c)).collect()
+        }
+    }
+}
+
+/// The query as seen by a command handler.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Args<'q> {
+    path: &'q [&'q str],
+    args: &'q [&'q str],
+    rest: &'q str
+}
+
+impl<'q> Args<'q> {
+    /// Returns the words that selected the command, as typed.
+    pub fn path(&self) -> &'q [&'q str] {
+        self.path
+    }
+
+    /// Returns the words after the command.
+    pub fn args(&self) -> &'q [&'q str] {
+        self.args
+    }
+
+    /// Returns the query after the command, without leading whitespace.
+    ///
+    /// Unlike `args()`, this keeps the whitespace between words, which is useful for commands
+    /// that take free-form text such as a search query.
+    pub fn rest(&self) -> &'q str {
+        self.rest
+    }
+}
+
+fn completion_item<'a>(path: &[&str], node: &Node) -> Item<'a> {
+    let mut autocomplete = String::new();
+    for word in path {
+        autocomplete.push_str(word);
+        autocomplete.push(' ');
+    }
+    autocomplete.push_str(&node.name);
+    autocomplete.push(' ');
+    let mut builder = ItemBuilder::new(node.name.clone()).autocomplete(autocomplete).valid(false);
+    if let Some(ref description) = node.description {
+        builder = builder.subtitle(description.clone());
+    }
+    builder.into_item()
+}
+
+/// Returns `query` without its first `words` words and the whitespace following them.
+fn rest(query: &str, words: usize) -> &str {
+    let mut rest = query.trim_start();
+    for _ in 0..words {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+    rest
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[test]
+fn test_route() {
+    let router = Router::new()
+                        .group("pr", "Pull requests")
+                        .command("pr list", "List pull requests", |args| {
+                            vec![Item::new(format!("list {:?}", args.args()))]
+                        })
+                        .command("pr listall", "List all pull requests", |_| vec![])
+                        .command("pr view", "View a pull request", |args| {
+                            vec![Item::new(format!("view {:?} {:?}", args.path(), args.rest()))]
+                        })
+                        .command("issue", "Search issues", |args| {
+                            vec![Item::new(format!("issue {:?}", args.rest()))]
+                        })
+                        .command("issue new", "Open an issue", |_| vec![Item::new("new")]);
+    let route = |query| {
+        router.route(query).iter().map(|item| {
+            match item.autocomplete {
+                Some(ref autocomplete) => format!("{} -> {:?}", item.title, autocomplete),
+                None => item.title.to_string()
+            }
+        }).collect::<Vec<_>>()
+    };
+
+    let cases: &[(&str, &[&str])] = &[
+        ("", &["pr -> \"pr \"", "issue -> \"issue \""]),
+        ("  ", &["pr -> \"pr \"", "issue -> \"issue \""]),
+        ("P", &["pr -> \"pr \""]),
+        ("pr", &["list -> \"pr list \"", "listall -> \"pr listall \"", "view -> \"pr view \""]),
+        ("pr ", &["list -> \"pr list \"", "listall -> \"pr listall \"", "view -> \"pr view \""]),
+        ("pr li", &["list -> \"pr list \"", "listall -> \"pr listall \""]),
+        ("pr list", &["list -> \"pr list \"", "listall -> \"pr listall \""]),
+        ("pr list ", &["list []"]),
+        ("pr listall", &[]),
+        ("PR View", &["view [\"PR\", \"View\"] \"\""]),
+        ("pr view  fix  the build ", &["view [\"pr\", \"view\"] \"fix  the build \""]),
+        ("pr merge", &["Unknown command “merge” -> \"pr \""]),
+        ("pr merge 1", &["Unknown command “merge” -> \"pr \""]),
+        ("wiki", &["Unknown command “wiki” -> \"\""]),
+        ("issue", &["issue \"\""]),
+        ("issue ", &["issue \"\""]),
+        ("issue n", &["new -> \"issue new \""]),
+        ("issue new", &["new"]),
+        ("issue newest", &["issue \"newest\""]),
+        ("issue crash on launch", &["issue \"crash on launch\""]),
+    ];
+    for &(query, expected) in cases {
+        assert_eq!(route(query), expected, "route({:?})", query);
+    }
+
+    let items = router.route("pr");
+    assert!(items.iter().all(|item| !item.valid));
+    assert_eq!(items[2].subtitle.as_deref(), Some("View a pull request"));
+    assert_eq!(router.route("p")[0].subtitle.as_deref(), Some("Pull requests"));
+    assert_eq!(router.route("pr merge")[0].subtitle.as_deref(), Some("Commands: list, listall, view"));
+}
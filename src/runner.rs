@@ -0,0 +1,234 @@
+//! Entry point for script filters that turns errors into items
+//!
+//! A script filter that fails without writing any output leaves Alfred showing nothing at all,
+//! which is indistinguishable from having no results. The functions in this module run a closure
+//! that produces items and write them out, and if the closure fails, they write a single invalid
+//! item describing the error instead.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # use std::io;
+//! #
+//! fn items() -> io::Result<Vec<alfred::Item<'static>>> {
+//!     Ok(vec![alfred::Item::new("Item 1")])
+//! }
+//!
+//! fn main() {
+//!     alfred::runner::run(items);
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::error;
+use std::io::{self, Write};
+
+use crash;
+use env::Environment;
+use {json, xml, Icon, Item, ItemBuilder};
+
+/// The icon used for error items.
+pub(crate) const ERROR_ICON: &str = "/System/Library/CoreServices/CoreTypes.bundle/Contents/Resources/\
+                          AlertStopIcon.icns";
+
+/// The script filter output format.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Format {
+    /// JSON output (Alfred 3 or later), written with `alfred::json`.
+    Json,
+    /// XML output (Alfred 2), written with `alfred::xml`.
+    Xml
+}
+
+/// Runs script filters and writes their output.
+///
+/// The free function `run()` covers the common case of JSON output to stdout. A `Runner` can be
+/// used to pick the output format or to supply the `Environment` used to check for debug mode.
+#[derive(Clone,Debug)]
+pub struct Runner {
+    format: Format,
+    env: Environment
+}
+
+impl Default for Runner {
+    fn default() -> Runner {
+        Runner::new()
+    }
+}
+
+impl Runner {
+    /// Returns a new `Runner` that writes JSON and reads the process environment.
+    pub fn new() -> Runner {
+        Runner {
+            format: Format::Json,
+            env: Environment::from_env()
+        }
+    }
+
+    /// Sets the output format.
+    pub fn format(mut self, format: Format) -> Runner {
+        self.format = format;
+        self
+    }
+
+    /// Sets the environment used to check whether the workflow debugger is open.
+    pub fn environment(mut self, env: Environment) -> Runner {
+        self.env = env;
+        self
+    }
+
+    /// Runs `f` and writes its items to stdout.
+    ///
+    /// Output is written through `alfred::crash::stdout()`, so a panic hook installed with
+    /// `alfred::crash::install()` won't write a second document.
+    ///
+    /// See `run_to()`.
+    pub fn run<'a, F, E>(&self, f: F) -> io::Result<()>
+        where F: FnOnce() -> Result<Vec<Item<'a>>, E>,
+              E: Into<Box<dyn error::Error>>
+    {
+        self.run_to(crash::stdout(), f)
+    }
+
+    /// Runs `f` and writes its items to `w`.
+    ///
+    /// If `f` returns an error, a single item describing the error is written instead. See
+    /// `error_item()` for details. When the workflow debugger is open, the error and its causes
+    /// are also written to stderr, which Alfred shows in the debugger.
+    ///
+    /// The `Write` is flushed after the output is written.
+    pub fn run_to<'a, W, F, E>(&self, w: W, f: F) -> io::Result<()>
+        where W: Write,
+              F: FnOnce() -> Result<Vec<Item<'a>>, E>,
+              E: Into<Box<dyn error::Error>>
+    {
+        match f() {
+            Ok(items) => self.write(w, &items),
+            Err(err) => {
+                let err = err.into();
+                let debug = self.env.is_debug();
+                if debug {
+                    let chain = cause_chain(&*err);
+                    let _ = writeln!(io::stderr(), "Error: {}", chain.join("\n  caused by: "));
+                }
+                self.write(w, &[error_item(&*err, debug)])
+            }
+        }
+    }
+
+    fn write(&self, w: impl Write, items: &[Item]) -> io::Result<()> {
+        match self.format {
+            Format::Json => json::write_items(w, items),
+            Format::Xml => xml::write_items(w, items)
+        }
+    }
+}
+
+/// Runs `f` and writes its items to stdout as JSON.
+///
+/// If `f` returns an error, a single item describing the error is written instead. If the output
+/// itself can't be written, the error is printed to stderr.
+///
+/// Use a `Runner` for XML output or more control.
+pub fn run<'a, F, E>(f: F)
+    where F: FnOnce() -> Result<Vec<Item<'a>>, E>,
+          E: Into<Box<dyn error::Error>>
+{
+    if let Err(err) = Runner::new().run(f) {
+        let _ = writeln!(io::stderr(), "Error writing items: {}", err);
+    }
+}
+
+/// Returns an item describing `err`.
+///
+/// The item is not `valid`, so actioning it does nothing. Its title is the error message and the
+/// full message, including all causes, can be copied or shown in large type.
+///
+/// When `debug` is `false`, the subtitle is the root cause of the error if there is one, or a
+/// hint to open the workflow debugger otherwise. When `debug` is `true`, the subtitle shows the
+/// full cause chain.
+pub fn error_item(err: &dyn error::Error, debug: bool) -> Item<'static> {
+    let chain = cause_chain(err);
+    let full = chain.join(": ");
+    let subtitle = if debug {
+        match chain.len() {
+            1 => "No further details".to_owned(),
+            _ => chain[1..].join(": ")
+        }
+    } else if chain.len() > 1 {
+        chain[chain.len()-1].clone()
+    } else {
+        "Open the workflow debugger for details".to_owned()
+    };
+    ItemBuilder::new(Cow::Owned(format!("Error: {}", chain[0])))
+                .subtitle(subtitle)
+                .icon(Icon::Path(Cow::Borrowed(ERROR_ICON)))
+                .valid(false)
+                .text_copy(full.clone())
+                .text_large_type(full)
+                .into_item()
+}
+
+fn cause_chain(err: &dyn error::Error) -> Vec<String> {
+    let mut chain = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push(err.to_string());
+        source = err.source();
+    }
+    chain
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct TestError(&'static str, Option<Box<TestError>>);
+
+#[cfg(test)]
+impl ::std::fmt::Display for TestError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(test)]
+impl error::Error for TestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.1.as_ref().map(|e| &**e as &(dyn error::Error + 'static))
+    }
+}
+
+#[test]
+fn test_run() {
+    let runner = Runner::new().environment(Environment::new());
+    let mut output = Vec::new();
+    runner.run_to(&mut output, || Ok::<_, io::Error>(vec![Item::new("Item 1")])).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), r#"{"items":[{"title":"Item 1"}]}"#);
+
+    let err = || -> Result<Vec<Item>, _> {
+        Err(TestError("fetching results", Some(Box::new(TestError("connection refused", None)))))
+    };
+    let mut output = Vec::new();
+    runner.run_to(&mut output, err).unwrap();
+    let output: ::serde_json::Value = ::serde_json::from_slice(&output).unwrap();
+    assert_eq!(output["items"][0]["title"], "Error: fetching results");
+    assert_eq!(output["items"][0]["subtitle"], "connection refused");
+    assert_eq!(output["items"][0]["valid"], false);
+    assert_eq!(output["items"][0]["text"]["copy"], "fetching results: connection refused");
+
+    let mut output = Vec::new();
+    runner.clone().format(Format::Xml).run_to(&mut output, || Err("oops")).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(r#"valid="no""#));
+    assert!(output.contains("<title>Error: oops</title>"));
+}
+
+#[test]
+fn test_error_item() {
+    let err = TestError("a", Some(Box::new(TestError("b", Some(Box::new(TestError("c", None)))))));
+    assert_eq!(error_item(&err, false).subtitle.as_deref(), Some("c"));
+    assert_eq!(error_item(&err, true).subtitle.as_deref(), Some("b: c"));
+    let err = TestError("a", None);
+    assert_eq!(error_item(&err, false).subtitle.as_deref(),
+               Some("Open the workflow debugger for details"));
+}
@@ -0,0 +1,269 @@
+//! Helpers for writing Alfred Text View JSON output (Alfred 5.5)
+//!
+//! A Text View shows a block of plain text or Markdown instead of a list of items. When driven
+//! by a script, the script writes a JSON document with the text to show, and optionally a footer,
+//! how the new text is combined with what's already shown, and a rerun interval.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # use std::io::{self, Write};
+//! #
+//! # fn write_response() -> io::Result<()> {
+//! use alfred::textview::{Builder, ResponseMode, Scroll};
+//!
+//! Builder::new("# Build log\n\nStep 1 done")
+//!         .footer("Press ⌘C to copy")
+//!         .response_mode(ResponseMode::Append)
+//!         .scroll(Scroll::End)
+//!         .rerun(1.0)
+//!         .variable("step", "1")
+//!         .write(io::stdout())
+//! # }
+//! #
+//! # fn main() {
+//! #     if let Err(err) = write_response() {
+//! #         let _ = writeln!(&mut io::stderr(), "Error writing response: {}", err);
+//! #     }
+//! # }
+//! ```
+
+use serde_json as json;
+use serde_json::value::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+
+/// Writes a complete JSON document showing `response` to the `Write`.
+///
+/// The `Write` is flushed after the JSON document is written.
+pub fn write_response<'a, W: Write, S: Into<Cow<'a, str>>>(w: W, response: S) -> io::Result<()> {
+    Builder::new(response).write(w)
+}
+
+/// How the text of a response is combined with the text already shown.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum ResponseMode {
+    /// Replace the text already shown. This is Alfred's default.
+    Replace,
+    /// Add the text after the text already shown.
+    Append,
+    /// Add the text before the text already shown.
+    Prepend
+}
+
+impl ResponseMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            ResponseMode::Replace => "replace",
+            ResponseMode::Append => "append",
+            ResponseMode::Prepend => "prepend"
+        }
+    }
+}
+
+/// Where the Text View scrolls to after showing a response.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Scroll {
+    /// Let Alfred decide. This is Alfred's default.
+    Auto,
+    /// Scroll to the start of the text.
+    Start,
+    /// Scroll to the end of the text.
+    End
+}
+
+impl Scroll {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scroll::Auto => "auto",
+            Scroll::Start => "start",
+            Scroll::End => "end"
+        }
+    }
+}
+
+/// How the Text View behaves when it shows a response.
+///
+/// Unset options use Alfred's defaults.
+#[derive(Clone,Debug,PartialEq,Eq,Hash,Default)]
+pub struct Behaviour {
+    /// How the text is combined with the text already shown.
+    pub response: Option<ResponseMode>,
+    /// Where the view scrolls to.
+    pub scroll: Option<Scroll>,
+    /// Whether the text in the input field is selected.
+    pub select_input: bool
+}
+
+impl Behaviour {
+    /// Serializes the `Behaviour` into its JSON representation.
+    pub fn to_json(&self) -> Value {
+        let mut d = json::Map::new();
+        if let Some(mode) = self.response {
+            d.insert("response".to_owned(), json!(mode.as_str()));
+        }
+        if let Some(scroll) = self.scroll {
+            d.insert("scroll".to_owned(), json!(scroll.as_str()));
+        }
+        if self.select_input {
+            d.insert("inputfield".to_owned(), json!("select"));
+        }
+        Value::Object(d)
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Behaviour::default()
+    }
+}
+
+/// A helper type for writing a Text View response.
+#[derive(Clone,Debug,Default)]
+pub struct Builder<'a> {
+    /// The text or Markdown to show.
+    pub response: Cow<'a, str>,
+    /// The text shown in the footer.
+    pub footer: Option<Cow<'a, str>>,
+    /// How the view behaves when it shows the response.
+    pub behaviour: Behaviour,
+    /// The interval in seconds after which the script is run again, between 0.1 and 5.
+    pub rerun: Option<f64>,
+    /// The variables that will be written out.
+    pub variables: HashMap<Cow<'a, str>, Cow<'a, str>>
+}
+
+impl<'a> Builder<'a> {
+    /// Returns a new `Builder` that shows `response`.
+    pub fn new<S: Into<Cow<'a, str>>>(response: S) -> Builder<'a> {
+        Builder { response: response.into(), ..Builder::default() }
+    }
+
+    /// Writes a complete JSON document representing the response to the `Write`.
+    ///
+    /// The `Write` is flushed after the JSON document is written.
+    pub fn write<W: Write>(self, mut w: W) -> io::Result<()> {
+        write!(&mut w, "{}", self.into_json())?;
+        w.flush()
+    }
+
+    /// Serializes the response into its JSON representation.
+    pub fn into_json(self) -> Value {
+        let mut root = json::Map::new();
+        root.insert("response".to_owned(), json!(self.response));
+        if let Some(footer) = self.footer {
+            root.insert("footer".to_owned(), json!(footer));
+        }
+        if !self.behaviour.is_empty() {
+            root.insert("behaviour".to_owned(), self.behaviour.to_json());
+        }
+        if let Some(rerun) = self.rerun {
+            root.insert("rerun".to_owned(), json!(rerun));
+        }
+        if !self.variables.is_empty() {
+            let vars = self.variables.into_iter()
+                                     .map(|(k, v)| (k.into_owned(), json!(v)))
+                                     .collect();
+            root.insert("variables".to_owned(), Value::Object(vars));
+        }
+        Value::Object(root)
+    }
+
+    /// Sets the text shown in the footer.
+    pub fn footer<S: Into<Cow<'a, str>>>(mut self, footer: S) -> Builder<'a> {
+        self.set_footer(footer);
+        self
+    }
+
+    /// Sets how the text is combined with the text already shown.
+    pub fn response_mode(mut self, mode: ResponseMode) -> Builder<'a> {
+        self.set_response_mode(mode);
+        self
+    }
+
+    /// Sets where the view scrolls to after showing the response.
+    pub fn scroll(mut self, scroll: Scroll) -> Builder<'a> {
+        self.set_scroll(scroll);
+        self
+    }
+
+    /// Sets whether the text in the input field is selected.
+    pub fn select_input(mut self, flag: bool) -> Builder<'a> {
+        self.set_select_input(flag);
+        self
+    }
+
+    /// Sets the interval in seconds after which the script is run again.
+    ///
+    /// Alfred accepts intervals between 0.1 and 5 seconds.
+    pub fn rerun(mut self, seconds: f64) -> Builder<'a> {
+        self.set_rerun(seconds);
+        self
+    }
+
+    /// Inserts a new variable into the builder's variables.
+    pub fn variable<K, V>(mut self, key: K, value: V) -> Builder<'a>
+        where K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.set_variable(key, value);
+        self
+    }
+
+    /// Sets the text shown in the footer.
+    pub fn set_footer<S: Into<Cow<'a, str>>>(&mut self, footer: S) {
+        self.footer = Some(footer.into());
+    }
+
+    /// Sets how the text is combined with the text already shown.
+    pub fn set_response_mode(&mut self, mode: ResponseMode) {
+        self.behaviour.response = Some(mode);
+    }
+
+    /// Sets where the view scrolls to after showing the response.
+    pub fn set_scroll(&mut self, scroll: Scroll) {
+        self.behaviour.scroll = Some(scroll);
+    }
+
+    /// Sets whether the text in the input field is selected.
+    pub fn set_select_input(&mut self, flag: bool) {
+        self.behaviour.select_input = flag;
+    }
+
+    /// Sets the interval in seconds after which the script is run again.
+    pub fn set_rerun(&mut self, seconds: f64) {
+        self.rerun = Some(seconds);
+    }
+
+    /// Inserts a new variable into the builder's variables.
+    pub fn set_variable<K, V>(&mut self, key: K, value: V)
+        where K: Into<Cow<'a, str>>,
+              V: Into<Cow<'a, str>>
+    {
+        self.variables.insert(key.into(), value.into());
+    }
+}
+
+#[test]
+fn test_write() {
+    let mut output = Vec::new();
+    write_response(&mut output, "Hello").unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), r#"{"response":"Hello"}"#);
+
+    let json = Builder::new(String::from("**bold**"))
+                       .footer("footer")
+                       .response_mode(ResponseMode::Prepend)
+                       .scroll(Scroll::Start)
+                       .select_input(true)
+                       .rerun(0.5)
+                       .variable("key", "value")
+                       .into_json();
+    assert_eq!(json, json!({
+        "response": "**bold**",
+        "footer": "footer",
+        "behaviour": {"response": "prepend", "scroll": "start", "inputfield": "select"},
+        "rerun": 0.5,
+        "variables": {"key": "value"}
+    }));
+}
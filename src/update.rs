@@ -0,0 +1,658 @@
+//! Checking a release feed for new versions of the workflow
+//!
+//! Workflows distributed as `.alfredworkflow` files have no built-in way of telling users about
+//! new versions. An `UpdateChecker` compares the running workflow's version
+//! (`alfred_workflow_version`) with the latest release in a feed, and produces an "Update
+//! available" item that can be prepended to the script filter results.
+//!
+//! The feed is JSON in the format of the GitHub releases API: either an array of releases or a
+//! single release, each with a `tag_name`, an `html_url`, optional `prerelease` and `draft` flags,
+//! and optional `assets` with a `name` and `browser_download_url`. If a release has an asset
+//! ending in `.alfredworkflow`, the update item opens that asset; otherwise it opens the release
+//! page. A release is a prerelease if it has the `prerelease` flag or its tag has a prerelease
+//! suffix such as `-beta.1`.
+//!
+//! The result of a check is cached in the workflow cache directory, and the feed is fetched again
+//! only once the check interval has passed, so checking on every invocation is cheap. This
+//! includes failed checks, so a workflow used offline doesn't wait for the fetch every time.
+//!
+//! Fetching is done by a `Fetcher`. The default `CurlFetcher` runs `curl`, which ships with
+//! macOS; any `Fn(&str) -> Result<Vec<u8>, FetchError>` can be used instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! use alfred::update::{Feed, UpdateChecker};
+//!
+//! # fn main() {
+//! let checker = UpdateChecker::new(Feed::github("lilyball/alfred-rs"));
+//! let mut items = vec![alfred::Item::new("Result")];
+//! if let Some(item) = checker.update_item() {
+//!     items.insert(0, item);
+//! }
+//! # }
+//! ```
+
+use serde_json::{self, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use env::Environment;
+use {Item, ItemBuilder};
+
+/// The default interval between checks of the release feed.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The error type returned by a `Fetcher`.
+pub type FetchError = Box<dyn error::Error + Send + Sync>;
+
+/// Fetches the contents of a URL.
+pub trait Fetcher {
+    /// Returns the body of the response for `url`.
+    ///
+    /// Non-successful HTTP statuses should be reported as errors.
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError>;
+}
+
+impl<F: Fn(&str) -> Result<Vec<u8>, FetchError>> Fetcher for F {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        self(url)
+    }
+}
+
+/// A `Fetcher` that runs `curl`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct CurlFetcher {
+    /// The path of the `curl` executable. The default is `/usr/bin/curl`.
+    pub program: PathBuf,
+    /// The maximum time the whole request may take. The default is 5 seconds.
+    pub timeout: Duration
+}
+
+impl Default for CurlFetcher {
+    fn default() -> CurlFetcher {
+        CurlFetcher {
+            program: PathBuf::from("/usr/bin/curl"),
+            timeout: Duration::from_secs(5)
+        }
+    }
+}
+
+impl Fetcher for CurlFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        let output = Command::new(&self.program)
+                             .args(["--silent", "--show-error", "--fail", "--location"])
+                             .arg("--max-time").arg(self.timeout.as_secs().max(1).to_string())
+                             .args(["--header", "Accept: application/json"])
+                             .arg("--")
+                             .arg(url)
+                             .output()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(format!("curl failed ({}): {}", output.status, stderr.trim()).into())
+        }
+    }
+}
+
+/// A release feed.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Feed {
+    url: String
+}
+
+impl Feed {
+    /// Returns the feed of GitHub releases for `repo`, given as `"owner/name"`.
+    pub fn github(repo: &str) -> Feed {
+        Feed { url: format!("https://api.github.com/repos/{}/releases", repo) }
+    }
+
+    /// Returns a feed at an arbitrary URL.
+    ///
+    /// The feed must use the same format as the GitHub releases API.
+    pub fn url<S: Into<String>>(url: S) -> Feed {
+        Feed { url: url.into() }
+    }
+
+    /// Returns the URL of the feed.
+    pub fn as_url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// A release from the feed.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Release {
+    /// The version of the release, parsed from its tag.
+    pub version: Version,
+    /// The URL of the release page.
+    pub url: String,
+    /// The URL of the `.alfredworkflow` asset of the release, if it has one.
+    pub download_url: Option<String>
+}
+
+/// An error produced while checking for updates.
+#[derive(Debug)]
+pub enum Error {
+    /// The workflow's current version is unknown or unparseable.
+    UnknownVersion,
+    /// The feed couldn't be fetched.
+    Fetch(FetchError),
+    /// The feed isn't valid.
+    Feed(String),
+    /// Reading or writing the cache failed.
+    Io(io::Error)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownVersion => f.write_str("the workflow version is unknown"),
+            Error::Fetch(ref err) => write!(f, "couldn't fetch the release feed: {}", err),
+            Error::Feed(ref msg) => write!(f, "invalid release feed: {}", msg),
+            Error::Io(ref err) => write!(f, "couldn't access the update cache: {}", err)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Fetch(ref err) => Some(&**err),
+            Error::Io(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// Checks a release feed for new versions of the workflow.
+pub struct UpdateChecker {
+    feed: Feed,
+    env: Environment,
+    current: Option<String>,
+    interval: Duration,
+    prereleases: bool,
+    cache_path: Option<PathBuf>,
+    fetcher: Box<dyn Fetcher>
+}
+
+impl fmt::Debug for UpdateChecker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpdateChecker")
+         .field("feed", &self.feed)
+         .field("current", &self.resolved_version())
+         .field("interval", &self.interval)
+         .field("prereleases", &self.prereleases)
+         .field("cache_path", &self.resolved_cache_path())
+         .finish()
+    }
+}
+
+impl UpdateChecker {
+    /// Returns a new `UpdateChecker` for the given feed that reads the process environment.
+    pub fn new(feed: Feed) -> UpdateChecker {
+        UpdateChecker {
+            feed,
+            env: Environment::from_env(),
+            current: None,
+            interval: DEFAULT_INTERVAL,
+            prereleases: false,
+            cache_path: None,
+            fetcher: Box::new(CurlFetcher::default())
+        }
+    }
+
+    /// Sets the environment used to find the workflow version and cache directory.
+    pub fn environment(mut self, env: Environment) -> UpdateChecker {
+        self.env = env;
+        self
+    }
+
+    /// Sets the current version of the workflow.
+    ///
+    /// The default is the value of `alfred_workflow_version`.
+    pub fn current_version<S: Into<String>>(mut self, version: S) -> UpdateChecker {
+        self.current = Some(version.into());
+        self
+    }
+
+    /// Sets the minimum time between fetches of the feed. The default is one day.
+    pub fn interval(mut self, interval: Duration) -> UpdateChecker {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets whether prereleases are considered. The default is `false`.
+    ///
+    /// Changing this invalidates the cached result.
+    pub fn include_prereleases(mut self, include: bool) -> UpdateChecker {
+        self.prereleases = include;
+        self
+    }
+
+    /// Sets the path of the cache file.
+    ///
+    /// The default is `update.json` in the workflow cache directory. If there's no cache
+    /// directory, the feed is fetched on every check.
+    pub fn cache_path<P: Into<PathBuf>>(mut self, path: P) -> UpdateChecker {
+        self.cache_path = Some(path.into());
+        self
+    }
+
+    /// Sets the `Fetcher` used to fetch the feed.
+    pub fn fetcher<F: Fetcher + 'static>(mut self, fetcher: F) -> UpdateChecker {
+        self.fetcher = Box::new(fetcher);
+        self
+    }
+
+    fn resolved_version(&self) -> Option<String> {
+        self.current.clone().or_else(|| self.env.workflow_version())
+    }
+
+    fn resolved_cache_path(&self) -> Option<PathBuf> {
+        self.cache_path.clone().or_else(|| self.env.workflow_cache().map(|p| p.join("update.json")))
+    }
+
+    /// Returns the latest release, if it's newer than the current version.
+    ///
+    /// The feed is only fetched if the cached result is older than the check interval. If
+    /// fetching or parsing the feed failed, the same error is returned until then.
+    pub fn check(&self) -> Result<Option<Release>, Error> {
+        let current = self.resolved_version()
+                          .and_then(|v| v.parse::<Version>().ok())
+                          .ok_or(Error::UnknownVersion)?;
+        let latest = match self.cached()? {
+            Some(latest) => latest,
+            None => {
+                let latest = self.fetch_latest();
+                self.store(latest.as_ref().map(Option::as_ref))?;
+                latest?
+            }
+        };
+        Ok(latest.filter(|r| r.version > current))
+    }
+
+    /// Returns an "Update available" item if a newer release exists.
+    ///
+    /// Errors are ignored, so this can be called unconditionally.
+    pub fn update_item(&self) -> Option<Item<'static>> {
+        let release = self.check().ok()??;
+        let current = self.resolved_version().unwrap_or_default();
+        let subtitle = format!("You have version {}. Press ↩ to download version {}.",
+                               current, release.version);
+        let url = release.download_url.unwrap_or(release.url);
+        Some(ItemBuilder::new(Cow::Owned(format!("Update available: {}", release.version)))
+                         .subtitle(subtitle)
+                         .uid("alfred-rs.update-available")
+                         .arg(url.clone())
+                         .quicklook_url(url.clone())
+                         .text_copy(url)
+                         .into_item())
+    }
+
+    /// Returns the cached latest release, or `None` if the cache is missing or stale.
+    ///
+    /// A cached failure is returned as an error.
+    fn cached(&self) -> Result<Option<Option<Release>>, Error> {
+        let path = match self.resolved_cache_path() {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into())
+        };
+        // A corrupt cache is treated the same as a missing one.
+        let cache: Value = match serde_json::from_slice(&data) {
+            Ok(cache) => cache,
+            Err(_) => return Ok(None)
+        };
+        let checked = match cache["checked"].as_u64() {
+            Some(checked) => UNIX_EPOCH + Duration::from_secs(checked),
+            None => return Ok(None)
+        };
+        if cache["feed"].as_str() != Some(self.feed.as_url())
+            || cache["prereleases"].as_bool() != Some(self.prereleases) {
+            return Ok(None);
+        }
+        match SystemTime::now().duration_since(checked) {
+            Ok(age) if age < self.interval => {}
+            _ => return Ok(None)
+        }
+        if let Some(message) = cache["error"]["fetch"].as_str() {
+            return Err(Error::Fetch(message.into()));
+        }
+        if let Some(message) = cache["error"]["feed"].as_str() {
+            return Err(Error::Feed(message.to_owned()));
+        }
+        Ok(Some(release_from_json(&cache["latest"]).ok()))
+    }
+
+    /// Stores the result of fetching the feed.
+    ///
+    /// Only failures to fetch or parse the feed are stored; other errors aren't expected here.
+    fn store(&self, latest: Result<Option<&Release>, &Error>) -> Result<(), Error> {
+        let path = match self.resolved_cache_path() {
+            Some(path) => path,
+            None => return Ok(())
+        };
+        let checked = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs())
+                                                                  .unwrap_or(0);
+        let error = match latest {
+            Err(Error::Fetch(err)) => json!({"fetch": err.to_string()}),
+            Err(Error::Feed(message)) => json!({"feed": message}),
+            Err(_) => return Ok(()),
+            Ok(_) => Value::Null
+        };
+        let latest = latest.ok().and_then(|latest| latest).map(|r| json!({
+            "tag_name": r.version.to_string(),
+            "html_url": r.url,
+            "assets": r.download_url.iter().map(|u| json!({
+                "name": "workflow.alfredworkflow",
+                "browser_download_url": u
+            })).collect::<Vec<_>>()
+        }));
+        let cache = json!({
+            "feed": self.feed.as_url(),
+            "prereleases": self.prereleases,
+            "checked": checked,
+            "latest": latest,
+            "error": error
+        });
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, cache.to_string())?;
+        Ok(())
+    }
+
+    fn fetch_latest(&self) -> Result<Option<Release>, Error> {
+        let body = self.fetcher.fetch(self.feed.as_url()).map_err(Error::Fetch)?;
+        let feed: Value = serde_json::from_slice(&body).map_err(|e| Error::Feed(e.to_string()))?;
+        let releases = match feed {
+            Value::Array(releases) => releases,
+            release @ Value::Object(_) => vec![release],
+            _ => return Err(Error::Feed("expected an array or object".to_owned()))
+        };
+        let mut latest: Option<Release> = None;
+        for release in &releases {
+            if release["draft"].as_bool() == Some(true) {
+                continue;
+            }
+            if !self.prereleases && release["prerelease"].as_bool() == Some(true) {
+                continue;
+            }
+            let release = match release_from_json(release) {
+                Ok(release) => release,
+                // Skip releases with tags that aren't versions.
+                Err(_) => continue
+            };
+            if !self.prereleases && !release.version.pre.is_empty() {
+                continue;
+            }
+            if latest.as_ref().is_none_or(|l| release.version > l.version) {
+                latest = Some(release);
+            }
+        }
+        Ok(latest)
+    }
+}
+
+fn release_from_json(release: &Value) -> Result<Release, Error> {
+    let tag = release["tag_name"].as_str()
+                                 .ok_or_else(|| Error::Feed("release has no tag_name".to_owned()))?;
+    let version = tag.parse().map_err(|_| Error::Feed(format!("invalid version `{}`", tag)))?;
+    let url = release["html_url"].as_str().unwrap_or_default().to_owned();
+    let download_url = release["assets"].as_array().and_then(|assets| {
+        assets.iter()
+              .filter(|a| a["name"].as_str().is_some_and(|n| n.ends_with(".alfredworkflow")))
+              .filter_map(|a| a["browser_download_url"].as_str())
+              .next()
+              .map(String::from)
+    });
+    Ok(Release { version, url, download_url })
+}
+
+/// A version number such as `1.2.3` or `v2.0.0-beta.1`.
+///
+/// Versions are compared by their numeric components, with missing components treated as zero.
+/// A version with a prerelease suffix is older than the same version without one, and prerelease
+/// suffixes are compared as in Semantic Versioning. Build metadata after a `+` is ignored.
+#[derive(Clone,Debug)]
+pub struct Version {
+    numbers: Vec<u64>,
+    pre: Vec<String>,
+    text: String
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        let number = |v: &Version, i: usize| v.numbers.get(i).cloned().unwrap_or(0);
+        for i in 0..len {
+            match number(self, i).cmp(&number(other, i)) {
+                Ordering::Equal => {}
+                ord => return ord
+            }
+        }
+        match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            (false, false) => {}
+        }
+        for (a, b) in self.pre.iter().zip(&other.pre) {
+            let ord = match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b)
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        self.pre.len().cmp(&other.pre.len())
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// An error returned when parsing a `Version` fails.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ParseVersionError(());
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid version number")
+    }
+}
+
+impl error::Error for ParseVersionError {}
+
+impl ::std::str::FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Version, ParseVersionError> {
+        let s = s.trim();
+        let text = s.trim_start_matches(['v', 'V']);
+        let without_build = text.split('+').next().unwrap_or_default();
+        let mut parts = without_build.splitn(2, '-');
+        let numbers = parts.next().unwrap_or_default()
+                           .split('.')
+                           .map(|n| n.parse::<u64>())
+                           .collect::<Result<Vec<_>, _>>()
+                           .map_err(|_| ParseVersionError(()))?;
+        let pre = match parts.next() {
+            Some("") => return Err(ParseVersionError(())),
+            Some(pre) => pre.split('.').map(String::from).collect(),
+            None => Vec::new()
+        };
+        Ok(Version { numbers, pre, text: text.to_owned() })
+    }
+}
+
+#[test]
+fn test_version() {
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+    assert_eq!(v("v1.2"), v("1.2.0"));
+    assert!(v("1.10.0") > v("1.9.3"));
+    assert!(v("2.0.0-beta.1") < v("2.0.0"));
+    assert!(v("2.0.0-beta.2") > v("2.0.0-beta.1"));
+    assert!(v("2.0.0-beta.11") > v("2.0.0-beta.2"));
+    assert!(v("2.0.0-alpha") < v("2.0.0-alpha.1"));
+    assert_eq!(v("1.0.0+build.5"), v("1.0.0"));
+    assert_eq!(v("v3.1").to_string(), "3.1");
+    assert!("1.x".parse::<Version>().is_err());
+    assert!("1.0-".parse::<Version>().is_err());
+    assert!("".parse::<Version>().is_err());
+}
+
+#[test]
+fn test_update_checker() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    const FEED: &str = r#"[
+        {"tag_name": "v2.0.0-beta.1", "html_url": "https://example.com/2.0.0-beta.1",
+         "prerelease": true},
+        {"tag_name": "v1.5.0", "html_url": "https://example.com/1.5.0", "prerelease": true},
+        {"tag_name": "v1.3.0", "html_url": "https://example.com/1.3.0", "draft": true},
+        {"tag_name": "v1.2.0", "html_url": "https://example.com/1.2.0",
+         "assets": [{"name": "Example.alfredworkflow",
+                     "browser_download_url": "https://example.com/Example.alfredworkflow"}]},
+        {"tag_name": "nightly", "html_url": "https://example.com/nightly"},
+        {"tag_name": "v1.1.0", "html_url": "https://example.com/1.1.0"}
+    ]"#;
+
+    // A stub HTTP server that serves the feed and counts requests.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/releases", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(0));
+    let counter = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            *counter.lock().unwrap() += 1;
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           FEED.len(), FEED);
+        }
+    });
+    let fetcher = {
+        let (url, curl) = (url.clone(), CurlFetcher::default());
+        move |u: &str| -> Result<Vec<u8>, FetchError> {
+            assert_eq!(u, url);
+            curl.fetch(u)
+        }
+    };
+
+    let dir = ::std::env::temp_dir().join(format!("alfred-update-test-{}", ::std::process::id()));
+    let env = Environment::from_map(vec![
+        ("alfred_workflow_version", "1.1.0"),
+        ("alfred_workflow_cache", dir.to_str().unwrap()),
+    ]);
+    let checker = UpdateChecker::new(Feed::url(url.clone()))
+                                .environment(env.clone())
+                                .fetcher(fetcher.clone());
+
+    let release = checker.check().unwrap().unwrap();
+    assert_eq!(release.version.to_string(), "1.2.0");
+    assert_eq!(release.download_url.as_deref(), Some("https://example.com/Example.alfredworkflow"));
+    assert_eq!(*requests.lock().unwrap(), 1);
+
+    // The second check is answered from the cache.
+    let item = checker.update_item().unwrap();
+    assert_eq!(item.title, "Update available: 1.2.0");
+    assert_eq!(item.arg.as_deref(), Some("https://example.com/Example.alfredworkflow"));
+    assert_eq!(*requests.lock().unwrap(), 1);
+
+    // Prereleases are considered when asked for, which invalidates the cache.
+    let checker = UpdateChecker::new(Feed::url(url.clone()))
+                                .environment(env.clone())
+                                .include_prereleases(true)
+                                .fetcher(fetcher.clone());
+    assert_eq!(checker.check().unwrap().unwrap().version.to_string(), "2.0.0-beta.1");
+    assert_eq!(*requests.lock().unwrap(), 2);
+    let checker = checker.current_version("2.0.0-beta.1");
+    assert_eq!(checker.check().unwrap(), None);
+    assert_eq!(*requests.lock().unwrap(), 2);
+
+    // An expired cache is refreshed.
+    let checker = UpdateChecker::new(Feed::url(url))
+                                .environment(env.clone())
+                                .interval(Duration::from_secs(0))
+                                .fetcher(fetcher);
+    assert_eq!(checker.check().unwrap().unwrap().version.to_string(), "1.2.0");
+    assert_eq!(*requests.lock().unwrap(), 3);
+
+    // Failed fetches are cached too.
+    let failures = Arc::new(Mutex::new(0));
+    let counter = failures.clone();
+    let failing = move |_: &str| -> Result<Vec<u8>, FetchError> {
+        *counter.lock().unwrap() += 1;
+        Err("offline".into())
+    };
+    let failing_checker = UpdateChecker::new(Feed::url("https://example.com/releases"))
+                                        .environment(env.clone())
+                                        .cache_path(dir.join("failing.json"))
+                                        .fetcher(failing);
+    for _ in 0..3 {
+        let err = failing_checker.check().unwrap_err();
+        assert_eq!(err.to_string(), "couldn't fetch the release feed: offline");
+        assert!(failing_checker.update_item().is_none());
+    }
+    assert_eq!(*failures.lock().unwrap(), 1);
+    let failing_checker = failing_checker.interval(Duration::from_secs(0));
+    assert!(failing_checker.check().is_err());
+    assert_eq!(*failures.lock().unwrap(), 2);
+
+    // No item when up to date.
+    let checker = checker.current_version("2.0.0");
+    assert!(checker.update_item().is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
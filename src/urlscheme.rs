@@ -0,0 +1,254 @@
+//! Alfred's `alfred://` URL scheme
+//!
+//! Alfred handles `alfred://` URLs by performing an action, such as running a workflow's external
+//! trigger. These URLs are useful as the `arg` or `quicklook_url` of items, but every component
+//! has to be percent-encoded correctly. `AlfredUrl` describes a URL and encodes it when formatted
+//! with `Display`, and parses it back with `FromStr`.
+//!
+//! The bundle identifier in a URL usually refers to the running workflow. `Builder` fills it in
+//! from `alfred::env::workflow_bundle_id()`.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::urlscheme::{AlfredUrl, Builder};
+//!
+//! let builder = Builder::new().bundle_id("com.example.wf");
+//! let url = builder.run_trigger("open", Some("a&b c")).unwrap();
+//! assert_eq!(url.to_string(), "alfred://runtrigger/com.example.wf/open/?argument=a%26b%20c");
+//! assert_eq!(url.to_string().parse::<AlfredUrl>(), Ok(url));
+//! # }
+//! ```
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use env::Environment;
+
+/// An `alfred://` URL.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub enum AlfredUrl {
+    /// Runs an external trigger of a workflow, optionally with an argument.
+    RunTrigger {
+        /// The bundle identifier of the workflow.
+        bundle_id: String,
+        /// The identifier of the external trigger.
+        trigger: String,
+        /// The argument passed to the trigger.
+        argument: Option<String>
+    },
+    /// Opens Alfred with the given query.
+    Search {
+        /// The query.
+        query: String
+    },
+    /// Opens the user configuration of a workflow (Alfred 5 or later).
+    UserConfig {
+        /// The bundle identifier of the workflow.
+        bundle_id: String
+    }
+}
+
+impl fmt::Display for AlfredUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlfredUrl::RunTrigger { ref bundle_id, ref trigger, ref argument } => {
+                write!(f, "alfred://runtrigger/{}/{}/", Encoded(bundle_id), Encoded(trigger))?;
+                if let Some(ref argument) = *argument {
+                    write!(f, "?argument={}", Encoded(argument))?;
+                }
+                Ok(())
+            }
+            AlfredUrl::Search { ref query } => write!(f, "alfred://search/{}", Encoded(query)),
+            AlfredUrl::UserConfig { ref bundle_id } => {
+                write!(f, "alfred://userconfig/{}/", Encoded(bundle_id))
+            }
+        }
+    }
+}
+
+impl FromStr for AlfredUrl {
+    type Err = ParseUrlError;
+
+    fn from_str(s: &str) -> Result<AlfredUrl, ParseUrlError> {
+        let rest = match s.find("://") {
+            Some(i) if s[..i].eq_ignore_ascii_case("alfred") => &s[i+3..],
+            _ => return Err(ParseUrlError(()))
+        };
+        let (path, query) = match rest.find('?') {
+            Some(i) => (&rest[..i], Some(&rest[i+1..])),
+            None => (rest, None)
+        };
+        let (action, path) = match path.find('/') {
+            Some(i) => (&path[..i], &path[i+1..]),
+            None => (path, "")
+        };
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut params = Vec::new();
+        for pair in query.into_iter().flat_map(|q| q.split('&')).filter(|p| !p.is_empty()) {
+            let (key, value) = match pair.find('=') {
+                Some(i) => (&pair[..i], &pair[i+1..]),
+                None => (pair, "")
+            };
+            params.push((decode(key)?, decode(value)?));
+        }
+
+        match (&*action.to_ascii_lowercase(), &segments[..]) {
+            ("runtrigger", [bundle_id, trigger]) => Ok(AlfredUrl::RunTrigger {
+                bundle_id: decode(bundle_id)?,
+                trigger: decode(trigger)?,
+                argument: params.into_iter().find(|(k, _)| k == "argument").map(|(_, v)| v)
+            }),
+            ("search", _) if query.is_none() => Ok(AlfredUrl::Search { query: decode(path)? }),
+            ("userconfig", [bundle_id]) => Ok(AlfredUrl::UserConfig {
+                bundle_id: decode(bundle_id)?
+            }),
+            _ => Err(ParseUrlError(()))
+        }
+    }
+}
+
+/// An error returned when parsing an `AlfredUrl` fails.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ParseUrlError(());
+
+impl fmt::Display for ParseUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid Alfred URL")
+    }
+}
+
+impl error::Error for ParseUrlError {}
+
+/// Helper for creating `AlfredUrl`s that refer to a workflow.
+///
+/// The bundle identifier defaults to the running workflow's.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Builder {
+    bundle_id: Option<String>
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Returns a new `Builder` that uses the bundle identifier from the process environment.
+    pub fn new() -> Builder {
+        Builder::from_environment(&Environment::from_env())
+    }
+
+    /// Returns a new `Builder` that uses the bundle identifier from `env`.
+    pub fn from_environment(env: &Environment) -> Builder {
+        Builder {
+            bundle_id: env.workflow_bundle_id()
+        }
+    }
+
+    /// Sets the bundle identifier of the workflow.
+    pub fn bundle_id<S: Into<String>>(mut self, bundle_id: S) -> Builder {
+        self.bundle_id = Some(bundle_id.into());
+        self
+    }
+
+    /// Returns a URL that runs the workflow's external trigger with the given identifier.
+    ///
+    /// Returns `None` if the bundle identifier isn't known.
+    pub fn run_trigger(&self, trigger: &str, argument: Option<&str>) -> Option<AlfredUrl> {
+        self.bundle_id.as_ref().map(|bundle_id| AlfredUrl::RunTrigger {
+            bundle_id: bundle_id.clone(),
+            trigger: trigger.to_owned(),
+            argument: argument.map(str::to_owned)
+        })
+    }
+
+    /// Returns a URL that opens the workflow's user configuration.
+    ///
+    /// Returns `None` if the bundle identifier isn't known.
+    pub fn user_config(&self) -> Option<AlfredUrl> {
+        self.bundle_id.as_ref().map(|bundle_id| AlfredUrl::UserConfig {
+            bundle_id: bundle_id.clone()
+        })
+    }
+
+    /// Returns a URL that opens Alfred with the given query.
+    pub fn search(&self, query: &str) -> AlfredUrl {
+        AlfredUrl::Search { query: query.to_owned() }
+    }
+}
+
+/// Formats a string with every byte other than the URL "unreserved" characters percent-encoded.
+struct Encoded<'a>(&'a str);
+
+impl<'a> fmt::Display for Encoded<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &b in self.0.as_bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    fmt::Write::write_char(f, b as char)?;
+                }
+                _ => write!(f, "%{:02X}", b)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes percent-encoded bytes in `s`.
+///
+/// Returns `None` if a `%` isn't followed by two hex digits, or if the result isn't valid UTF-8.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i+1..i+3).filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn decode(s: &str) -> Result<String, ParseUrlError> {
+    percent_decode(s).ok_or(ParseUrlError(()))
+}
+
+#[test]
+fn test_urls() {
+    let env = Environment::from_map(vec![("alfred_workflow_bundleid", "com.example.test")]);
+    let builder = Builder::from_environment(&env);
+    let cases = [
+        (builder.run_trigger("go", None).unwrap(), "alfred://runtrigger/com.example.test/go/"),
+        (builder.run_trigger("go", Some("50% off/ünïcode?=&+")).unwrap(),
+         "alfred://runtrigger/com.example.test/go/?argument=50%25%20off%2F%C3%BCn%C3%AFcode%3F%3D%26%2B"),
+        (builder.user_config().unwrap(), "alfred://userconfig/com.example.test/"),
+        (builder.search("g rust / serde"), "alfred://search/g%20rust%20%2F%20serde")
+    ];
+    for &(ref url, s) in &cases {
+        assert_eq!(url.to_string(), s);
+        assert_eq!(s.parse::<AlfredUrl>().as_ref(), Ok(url));
+    }
+    assert_eq!(Builder::from_environment(&Environment::new()).user_config(), None);
+
+    assert_eq!("ALFRED://runtrigger/a/b?argument=x".parse(),
+               Ok(AlfredUrl::RunTrigger {
+                   bundle_id: "a".to_owned(),
+                   trigger: "b".to_owned(),
+                   argument: Some("x".to_owned())
+               }));
+    for s in &["http://runtrigger/a/b/", "alfred://runtrigger/a/", "alfred://unknown/",
+               "alfred://search/%zz", "alfred://search/%FF", "alfred://search/%4",
+               "alfred://search/%+4"] {
+        assert_eq!(s.parse::<AlfredUrl>(), Err(ParseUrlError(())), "{}", s);
+    }
+}
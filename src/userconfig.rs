@@ -0,0 +1,748 @@
+//! Alfred 5 user configuration
+//!
+//! Alfred 5 workflows can declare configuration fields, which Alfred asks the user to fill in
+//! when the workflow is installed. The fields are stored in `info.plist` under
+//! `userconfigurationconfig`, and the values the user chose are passed to the workflow as
+//! environment variables.
+//!
+//! This module models the field definitions, so they can be read from `info.plist` or added to a
+//! generated one with `alfred::workflow::Workflow::user_config()`. `resolve()` turns the
+//! environment variables back into typed values, falling back to the declared defaults and
+//! checking the values against the definitions. A bad value produces a `ValueError`, which can be
+//! shown to the user as an item.
+//!
+//! This module requires the `workflow` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate alfred;
+//! # use std::io;
+//! fn main() {
+//!     let fields = alfred::userconfig::read("info.plist").unwrap();
+//!     let config = match alfred::userconfig::resolve_env(&fields) {
+//!         Ok(config) => config,
+//!         Err(err) => {
+//!             alfred::json::write_items(io::stdout(), &[err.item()]).unwrap();
+//!             return;
+//!         }
+//!     };
+//!     let limit = config.number("result_limit").unwrap_or(10.0);
+//!     # let _ = limit;
+//! }
+//! ```
+
+use plist::{self, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use config::expand_tilde;
+use env::Environment;
+use runner::ERROR_ICON;
+use {Icon, Item, ItemBuilder};
+
+/// An error reading field definitions.
+#[derive(Debug)]
+pub enum Error {
+    /// `info.plist` couldn't be read.
+    Plist(plist::Error),
+    /// A field definition is invalid. The message describes the problem.
+    Invalid(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Plist(ref err) => write!(f, "couldn't read info.plist: {}", err),
+            Error::Invalid(ref msg) => write!(f, "invalid user configuration: {}", msg)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Plist(ref err) => Some(err),
+            Error::Invalid(_) => None
+        }
+    }
+}
+
+impl From<plist::Error> for Error {
+    fn from(err: plist::Error) -> Error {
+        Error::Plist(err)
+    }
+}
+
+/// A user configuration field.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Field {
+    /// The environment variable the value is passed in.
+    pub variable: String,
+    /// The label shown next to the field.
+    pub label: String,
+    /// The description shown below the field.
+    pub description: String,
+    /// The kind of field, with its settings.
+    pub kind: FieldKind
+}
+
+/// The kinds of user configuration fields.
+#[derive(Clone,Debug,PartialEq)]
+pub enum FieldKind {
+    /// A single-line text field.
+    TextField {
+        /// The default value.
+        default: String,
+        /// The placeholder shown when the field is empty.
+        placeholder: String,
+        /// Whether a value is required.
+        required: bool,
+        /// Whether leading and trailing whitespace is removed.
+        trim: bool
+    },
+    /// A multi-line text field.
+    TextArea {
+        /// The default value.
+        default: String,
+        /// The placeholder shown when the field is empty.
+        placeholder: String,
+        /// Whether a value is required.
+        required: bool,
+        /// Whether leading and trailing whitespace is removed.
+        trim: bool
+    },
+    /// A pop-up menu.
+    PopupButton {
+        /// The value of the option selected by default.
+        default: String,
+        /// The options, as `(label, value)` pairs.
+        options: Vec<(String, String)>
+    },
+    /// A checkbox.
+    Checkbox {
+        /// Whether the checkbox is checked by default.
+        default: bool,
+        /// The text shown next to the checkbox.
+        text: String,
+        /// Whether the checkbox must be checked.
+        required: bool
+    },
+    /// A file or folder picker.
+    FilePicker {
+        /// The default path.
+        default: String,
+        /// The placeholder shown when no path is picked.
+        placeholder: String,
+        /// Whether a path is required.
+        required: bool,
+        /// What can be picked.
+        filter: FileFilter
+    },
+    /// A slider.
+    Slider {
+        /// The default value.
+        default: f64,
+        /// The minimum value.
+        min: f64,
+        /// The maximum value.
+        max: f64,
+        /// The number of tick marks.
+        markers: u64,
+        /// Whether the tick marks are shown.
+        show_markers: bool,
+        /// Whether only the values at tick marks can be picked.
+        only_markers: bool
+    }
+}
+
+/// What a `FieldKind::FilePicker` accepts.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum FileFilter {
+    /// Only files.
+    Files,
+    /// Only folders.
+    Folders,
+    /// Files and folders.
+    Any
+}
+
+impl Field {
+    /// Returns a new `Field` with an empty description.
+    pub fn new<V: Into<String>, L: Into<String>>(variable: V, label: L, kind: FieldKind) -> Field {
+        Field {
+            variable: variable.into(),
+            label: label.into(),
+            description: String::new(),
+            kind
+        }
+    }
+
+    /// Sets the description shown below the field.
+    pub fn description<S: Into<String>>(mut self, description: S) -> Field {
+        self.description = description.into();
+        self
+    }
+
+    /// Reads a field from its `info.plist` representation.
+    pub fn from_plist(plist: &Value) -> Result<Field, Error> {
+        let field = plist.as_dictionary().ok_or_else(|| invalid("a field isn't a dictionary"))?;
+        let string = |key: &str| field.get(key).and_then(Value::as_string).unwrap_or("").to_owned();
+        let variable = string("variable");
+        if variable.is_empty() {
+            return Err(invalid("a field has no variable"));
+        }
+        let config = field.get("config")
+                          .and_then(Value::as_dictionary)
+                          .ok_or_else(|| invalid(format!("`{}` has no config", variable)))?;
+        let config_string = |key: &str| {
+            config.get(key).and_then(Value::as_string).unwrap_or("").to_owned()
+        };
+        let config_bool = |key: &str| config.get(key).and_then(Value::as_boolean).unwrap_or(false);
+        let config_number = |key: &str| config.get(key).and_then(number).unwrap_or(0.0);
+
+        let kind = match field.get("type").and_then(Value::as_string) {
+            Some(kind @ "textfield") | Some(kind @ "textarea") => {
+                let default = config_string("default");
+                let placeholder = config_string("placeholder");
+                let (required, trim) = (config_bool("required"), config_bool("trim"));
+                if kind == "textfield" {
+                    FieldKind::TextField { default, placeholder, required, trim }
+                } else {
+                    FieldKind::TextArea { default, placeholder, required, trim }
+                }
+            }
+            Some("popupbutton") => {
+                let pairs = config.get("pairs").and_then(Value::as_array).map_or(&[][..], |p| p);
+                let options = pairs.iter().map(|pair| {
+                    let pair = pair.as_array().map_or(&[][..], |p| p);
+                    match (pair.first().and_then(Value::as_string),
+                           pair.get(1).and_then(Value::as_string)) {
+                        (Some(label), Some(value)) if pair.len() == 2 => {
+                            Ok((label.to_owned(), value.to_owned()))
+                        }
+                        _ => Err(invalid(format!("`{}` has an invalid option", variable)))
+                    }
+                }).collect::<Result<_, _>>()?;
+                FieldKind::PopupButton { default: config_string("default"), options }
+            }
+            Some("checkbox") => FieldKind::Checkbox {
+                default: config_bool("default"),
+                text: config_string("text"),
+                required: config_bool("required")
+            },
+            Some("filepicker") => FieldKind::FilePicker {
+                default: config_string("default"),
+                placeholder: config_string("placeholder"),
+                required: config_bool("required"),
+                filter: match config.get("filtermode").and_then(Value::as_unsigned_integer) {
+                    Some(0) => FileFilter::Files,
+                    Some(1) => FileFilter::Folders,
+                    _ => FileFilter::Any
+                }
+            },
+            // Unlike the other kinds, sliders store their default under `defaultvalue`.
+            Some("slider") => FieldKind::Slider {
+                default: config_number("defaultvalue"),
+                min: config_number("minvalue"),
+                max: config_number("maxvalue"),
+                markers: config.get("markercount").and_then(Value::as_unsigned_integer)
+                               .unwrap_or(0),
+                show_markers: config_bool("showmarkers"),
+                only_markers: config_bool("onlystoptomarkers")
+            },
+            Some(kind) => {
+                return Err(invalid(format!("`{}` has unknown type `{}`", variable, kind)));
+            }
+            None => return Err(invalid(format!("`{}` has no type", variable)))
+        };
+        Ok(Field {
+            variable,
+            label: string("label"),
+            description: string("description"),
+            kind
+        })
+    }
+
+    /// Returns the `info.plist` representation of the field.
+    pub fn to_plist(&self) -> Value {
+        let (kind, config) = match self.kind {
+            FieldKind::TextField { ref default, ref placeholder, required, trim } |
+            FieldKind::TextArea { ref default, ref placeholder, required, trim } => {
+                let kind = match self.kind {
+                    FieldKind::TextField { .. } => "textfield",
+                    _ => "textarea"
+                };
+                (kind, vec![
+                    ("default", string(default)),
+                    ("placeholder", string(placeholder)),
+                    ("required", Value::Boolean(required)),
+                    ("trim", Value::Boolean(trim))
+                ])
+            }
+            FieldKind::PopupButton { ref default, ref options } => ("popupbutton", vec![
+                ("default", string(default)),
+                ("pairs", Value::Array(options.iter().map(|(label, value)| {
+                    Value::Array(vec![string(label), string(value)])
+                }).collect()))
+            ]),
+            FieldKind::Checkbox { default, ref text, required } => ("checkbox", vec![
+                ("default", Value::Boolean(default)),
+                ("required", Value::Boolean(required)),
+                ("text", string(text))
+            ]),
+            FieldKind::FilePicker { ref default, ref placeholder, required, filter } => {
+                ("filepicker", vec![
+                    ("default", string(default)),
+                    ("filtermode", Value::Integer(match filter {
+                        FileFilter::Files => 0,
+                        FileFilter::Folders => 1,
+                        FileFilter::Any => 2
+                    }.into())),
+                    ("placeholder", string(placeholder)),
+                    ("required", Value::Boolean(required))
+                ])
+            }
+            FieldKind::Slider { default, min, max, markers, show_markers, only_markers } => {
+                ("slider", vec![
+                    ("defaultvalue", Value::Real(default)),
+                    ("markercount", Value::Integer(markers.into())),
+                    ("maxvalue", Value::Real(max)),
+                    ("minvalue", Value::Real(min)),
+                    ("onlystoptomarkers", Value::Boolean(only_markers)),
+                    ("showmarkers", Value::Boolean(show_markers))
+                ])
+            }
+        };
+        Value::Dictionary(vec![
+            ("config", Value::Dictionary(config.into_iter()
+                                               .map(|(k, v)| (k.to_owned(), v))
+                                               .collect())),
+            ("description", string(&self.description)),
+            ("label", string(&self.label)),
+            ("type", string(kind)),
+            ("variable", string(&self.variable))
+        ].into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    /// Returns the value of the field in `env`, or its default if the variable isn't set.
+    pub fn resolve(&self, env: &Environment) -> Result<FieldValue, ValueError> {
+        let raw = env.var(&self.variable);
+        let error = |message: String| Err(ValueError {
+            variable: self.variable.clone(),
+            message
+        });
+        match self.kind {
+            FieldKind::TextField { ref default, required, trim, .. } |
+            FieldKind::TextArea { ref default, required, trim, .. } => {
+                let value = raw.unwrap_or(default);
+                let value = if trim { value.trim() } else { value };
+                if required && value.is_empty() {
+                    return error(format!("{} is required", self.label));
+                }
+                Ok(FieldValue::Text(value.to_owned()))
+            }
+            FieldKind::PopupButton { ref default, ref options } => {
+                let value = raw.unwrap_or(default);
+                if options.iter().any(|(_, v)| v == value) {
+                    Ok(FieldValue::Text(value.to_owned()))
+                } else {
+                    let labels: Vec<_> = options.iter().map(|(l, _)| &**l).collect();
+                    error(format!("{} must be one of {}, not `{}`", self.label, labels.join(", "),
+                                  value))
+                }
+            }
+            FieldKind::Checkbox { default, required, .. } => {
+                let value = match raw {
+                    None => default,
+                    Some("1") | Some("true") => true,
+                    Some("0") | Some("false") | Some("") => false,
+                    Some(value) => {
+                        return error(format!("{} must be checked or unchecked, not `{}`",
+                                             self.label, value));
+                    }
+                };
+                if required && !value {
+                    return error(format!("{} must be checked", self.label));
+                }
+                Ok(FieldValue::Bool(value))
+            }
+            FieldKind::FilePicker { ref default, required, filter, .. } => {
+                let value = raw.unwrap_or(default).trim();
+                if value.is_empty() {
+                    if required {
+                        return error(format!("{} is required", self.label));
+                    }
+                    return Ok(FieldValue::Path(None));
+                }
+                let path = expand_tilde(value);
+                let ok = match filter {
+                    FileFilter::Files => path.is_file(),
+                    FileFilter::Folders => path.is_dir(),
+                    FileFilter::Any => path.exists()
+                };
+                if !ok {
+                    let what = match filter {
+                        FileFilter::Files => "file",
+                        FileFilter::Folders => "folder",
+                        FileFilter::Any => "file or folder"
+                    };
+                    return error(format!("{} must be an existing {}, not {}", self.label, what,
+                                         path.display()));
+                }
+                Ok(FieldValue::Path(Some(path)))
+            }
+            FieldKind::Slider { default, min, max, .. } => {
+                let value = match raw.map(str::trim) {
+                    None | Some("") => default,
+                    Some(value) => match value.parse::<f64>() {
+                        Ok(n) if n.is_finite() => n,
+                        _ => return error(format!("{} must be a number, not `{}`", self.label,
+                                                  value))
+                    }
+                };
+                if value < min || value > max {
+                    return error(format!("{} must be between {} and {}, not {}", self.label, min,
+                                         max, value));
+                }
+                Ok(FieldValue::Number(value))
+            }
+        }
+    }
+}
+
+fn invalid<S: Into<String>>(msg: S) -> Error {
+    Error::Invalid(msg.into())
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_owned())
+}
+
+fn number(value: &Value) -> Option<f64> {
+    value.as_real()
+         .or_else(|| value.as_signed_integer().map(|n| n as f64))
+         .or_else(|| value.as_string().and_then(|s| s.parse().ok()))
+}
+
+/// Reads the field definitions from the `info.plist` at `path`.
+///
+/// A workflow's working directory is its own directory, so `read("info.plist")` reads the
+/// running workflow's fields.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<Field>, Error> {
+    from_info_plist(&Value::from_file(path)?)
+}
+
+/// Reads the field definitions from the contents of `info.plist`.
+///
+/// Returns an empty list if the workflow has no user configuration.
+pub fn from_info_plist(plist: &Value) -> Result<Vec<Field>, Error> {
+    let fields = plist.as_dictionary().and_then(|d| d.get("userconfigurationconfig"));
+    match fields {
+        None => Ok(Vec::new()),
+        Some(fields) => fields.as_array()
+                              .ok_or_else(|| invalid("`userconfigurationconfig` isn't an array"))?
+                              .iter()
+                              .map(Field::from_plist)
+                              .collect()
+    }
+}
+
+/// The value of a user configuration field.
+#[derive(Clone,Debug,PartialEq)]
+pub enum FieldValue {
+    /// The value of a text field, text area, or pop-up menu.
+    Text(String),
+    /// The value of a checkbox.
+    Bool(bool),
+    /// The value of a file picker, with `~` expanded, or `None` if nothing was picked.
+    Path(Option<PathBuf>),
+    /// The value of a slider.
+    Number(f64)
+}
+
+/// A user configuration value that doesn't match its field definition.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ValueError {
+    variable: String,
+    message: String
+}
+
+impl ValueError {
+    /// Returns the name of the variable with the bad value.
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// Returns an item describing the error, which tells the user how to fix it.
+    pub fn item(&self) -> Item<'static> {
+        let hint = "Fix this in the workflow's configuration (Configure Workflow… in Alfred)";
+        ItemBuilder::new(self.message.clone())
+                    .subtitle(hint)
+                    .icon(Icon::Path(Cow::Borrowed(ERROR_ICON)))
+                    .valid(false)
+                    .text_copy(self.message.clone())
+                    .into_item()
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for ValueError {}
+
+/// The resolved values of a workflow's user configuration, keyed by variable name.
+#[derive(Clone,Debug,PartialEq,Default)]
+pub struct Config {
+    values: BTreeMap<String, FieldValue>
+}
+
+impl Config {
+    /// Returns the value of the given variable.
+    pub fn get(&self, variable: &str) -> Option<&FieldValue> {
+        self.values.get(variable)
+    }
+
+    /// Returns the value of a text field, text area, or pop-up menu.
+    pub fn text(&self, variable: &str) -> Option<&str> {
+        match self.get(variable) {
+            Some(FieldValue::Text(s)) => Some(s),
+            _ => None
+        }
+    }
+
+    /// Returns the value of a checkbox.
+    pub fn bool(&self, variable: &str) -> Option<bool> {
+        match self.get(variable) {
+            Some(&FieldValue::Bool(b)) => Some(b),
+            _ => None
+        }
+    }
+
+    /// Returns the value of a file picker, if a path was picked.
+    pub fn path(&self, variable: &str) -> Option<&Path> {
+        match self.get(variable) {
+            Some(FieldValue::Path(Some(path))) => Some(path),
+            _ => None
+        }
+    }
+
+    /// Returns the value of a slider.
+    pub fn number(&self, variable: &str) -> Option<f64> {
+        match self.get(variable) {
+            Some(&FieldValue::Number(n)) => Some(n),
+            _ => None
+        }
+    }
+
+    /// Returns an iterator over the variable names and values.
+    pub fn iter(&self) -> ::std::collections::btree_map::Iter<'_, String, FieldValue> {
+        self.values.iter()
+    }
+}
+
+/// Resolves each field to a typed value from `env`.
+///
+/// Returns the error for the first field with a bad value.
+pub fn resolve(fields: &[Field], env: &Environment) -> Result<Config, ValueError> {
+    let mut values = BTreeMap::new();
+    for field in fields {
+        values.insert(field.variable.clone(), field.resolve(env)?);
+    }
+    Ok(Config { values })
+}
+
+/// Resolves each field to a typed value from the process environment.
+///
+/// See `resolve()`.
+pub fn resolve_env(fields: &[Field]) -> Result<Config, ValueError> {
+    resolve(fields, &Environment::from_env())
+}
+
+#[cfg(test)]
+fn test_fields() -> Vec<Field> {
+    vec![
+        Field::new("api_key", "API Key", FieldKind::TextField {
+            default: String::new(),
+            placeholder: "Paste your key".to_owned(),
+            required: true,
+            trim: true
+        }).description("From your account settings"),
+        Field::new("region", "Region", FieldKind::PopupButton {
+            default: "eu".to_owned(),
+            options: vec![("Europe".to_owned(), "eu".to_owned()),
+                          ("United States".to_owned(), "us".to_owned())]
+        }),
+        Field::new("private", "Private", FieldKind::Checkbox {
+            default: false,
+            text: "Hide private results".to_owned(),
+            required: false
+        }),
+        Field::new("folder", "Folder", FieldKind::FilePicker {
+            default: String::new(),
+            placeholder: String::new(),
+            required: false,
+            filter: FileFilter::Folders
+        }),
+        Field::new("limit", "Result Limit", FieldKind::Slider {
+            default: 10.0,
+            min: 1.0,
+            max: 50.0,
+            markers: 0,
+            show_markers: false,
+            only_markers: false
+        })
+    ]
+}
+
+#[test]
+fn test_plist_round_trip() {
+    let fields = test_fields();
+    let mut plist = plist::Dictionary::new();
+    plist.insert("userconfigurationconfig".to_owned(),
+                 Value::Array(fields.iter().map(Field::to_plist).collect()));
+    let mut xml = Vec::new();
+    Value::Dictionary(plist).to_writer_xml(&mut xml).unwrap();
+    let plist = Value::from_reader_xml(&xml[..]).unwrap();
+    assert_eq!(from_info_plist(&plist).unwrap(), fields);
+    assert_eq!(from_info_plist(&Value::Dictionary(plist::Dictionary::new())).unwrap(), vec![]);
+}
+
+#[test]
+fn test_alfred_export() {
+    // The user configuration of a workflow exported by Alfred 5, trimmed to the relevant keys.
+    const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>userconfigurationconfig</key>
+	<array>
+		<dict>
+			<key>config</key>
+			<dict>
+				<key>default</key>
+				<string>~/Notes</string>
+				<key>placeholder</key>
+				<string></string>
+				<key>required</key>
+				<false/>
+				<key>trim</key>
+				<true/>
+			</dict>
+			<key>description</key>
+			<string>Where notes are saved</string>
+			<key>label</key>
+			<string>Notes Folder</string>
+			<key>type</key>
+			<string>textfield</string>
+			<key>variable</key>
+			<string>notes_dir</string>
+		</dict>
+		<dict>
+			<key>config</key>
+			<dict>
+				<key>defaultvalue</key>
+				<integer>20</integer>
+				<key>markercount</key>
+				<integer>11</integer>
+				<key>maxvalue</key>
+				<integer>50</integer>
+				<key>minvalue</key>
+				<integer>0</integer>
+				<key>onlystoptomarkers</key>
+				<false/>
+				<key>showmarkers</key>
+				<true/>
+			</dict>
+			<key>description</key>
+			<string></string>
+			<key>label</key>
+			<string>Result Limit</string>
+			<key>type</key>
+			<string>slider</string>
+			<key>variable</key>
+			<string>result_limit</string>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+    let plist = Value::from_reader_xml(INFO_PLIST.as_bytes()).unwrap();
+    let fields = from_info_plist(&plist).unwrap();
+    assert_eq!(fields[0].kind, FieldKind::TextField {
+        default: "~/Notes".to_owned(),
+        placeholder: String::new(),
+        required: false,
+        trim: true
+    });
+    assert_eq!(fields[1].kind, FieldKind::Slider {
+        default: 20.0,
+        min: 0.0,
+        max: 50.0,
+        markers: 11,
+        show_markers: true,
+        only_markers: false
+    });
+
+    // Writing the fields back produces the same keys.
+    let config = |field: &Field| {
+        let plist = field.to_plist();
+        let mut keys = plist.as_dictionary().unwrap()["config"].as_dictionary().unwrap()
+                            .keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    };
+    let exported = plist.as_dictionary().unwrap()["userconfigurationconfig"].as_array().unwrap();
+    for (field, exported) in fields.iter().zip(exported) {
+        let mut keys = exported.as_dictionary().unwrap()["config"].as_dictionary().unwrap()
+                               .keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(config(field), keys);
+    }
+    assert_eq!(resolve(&fields, &Environment::new()).unwrap().number("result_limit"), Some(20.0));
+}
+
+#[test]
+fn test_resolve() {
+    let fields = test_fields();
+    let dir = ::std::env::temp_dir();
+    let env = Environment::from_map(vec![
+        ("api_key", " abc "),
+        ("private", "1"),
+        ("folder", dir.to_str().unwrap()),
+        ("limit", "25")
+    ]);
+    let config = resolve(&fields, &env).unwrap();
+    assert_eq!(config.text("api_key"), Some("abc"));
+    assert_eq!(config.text("region"), Some("eu"));
+    assert_eq!(config.bool("private"), Some(true));
+    assert_eq!(config.path("folder"), Some(&*dir));
+    assert_eq!(config.number("limit"), Some(25.0));
+
+    let error = |vars: Vec<(&str, &str)>| {
+        let mut env = env.clone();
+        for (k, v) in vars {
+            env.set_var(k, v);
+        }
+        resolve(&fields, &env).unwrap_err().to_string()
+    };
+    assert_eq!(error(vec![("api_key", "  ")]), "API Key is required");
+    assert_eq!(error(vec![("region", "asia")]),
+               "Region must be one of Europe, United States, not `asia`");
+    assert_eq!(error(vec![("private", "maybe")]),
+               "Private must be checked or unchecked, not `maybe`");
+    assert_eq!(error(vec![("limit", "ten")]), "Result Limit must be a number, not `ten`");
+    assert_eq!(error(vec![("limit", "100")]), "Result Limit must be between 1 and 50, not 100");
+
+    let mut env = env.clone();
+    env.set_var("limit", "100");
+    let item = resolve(&fields, &env).unwrap_err().item();
+    assert_eq!(item.title, "Result Limit must be between 1 and 50, not 100");
+    assert!(!item.valid);
+}
@@ -0,0 +1,984 @@
+//! Generating `info.plist` from a description of the workflow
+//!
+//! Workflows are usually edited in Alfred's preferences, which makes changes hard to review. A
+//! `Workflow` describes the objects in a workflow, the connections between them, and their layout
+//! on the canvas, and writes the `info.plist` that Alfred reads, so a workflow can be generated
+//! from source.
+//!
+//! Objects are identified by a uid, which connections refer to. Alfred uses UUIDs for the uids of
+//! objects created in its editor, but any string that's unique within the workflow works.
+//!
+//! This module requires the `workflow` feature.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate alfred;
+//! # fn main() {
+//! use alfred::Modifier;
+//! use alfred::workflow::{Connection, Object, OpenUrl, RunScript, Script, ScriptFilter, Workflow};
+//!
+//! let workflow = Workflow::new("com.example.search", "Search")
+//!     .version("1.0.0")
+//!     .object(Object::new("filter", ScriptFilter::new("s", Script::file("./search")))
+//!                    .position(30.0, 30.0))
+//!     .object(Object::new("open", OpenUrl::new("{query}")).position(230.0, 30.0))
+//!     .object(Object::new("copy", RunScript::new(Script::bash("printf %s \"$1\" | pbcopy")))
+//!                    .position(230.0, 150.0))
+//!     .connection(Connection::new("filter", "open"))
+//!     .connection(Connection::new("filter", "copy").modifiers(&[Modifier::Command]));
+//!
+//! let mut plist = Vec::new();
+//! workflow.write(&mut plist).unwrap();
+//! # }
+//! ```
+
+use plist::{self, Dictionary, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use userconfig::Field;
+use Modifier;
+
+pub use escaping::Escaping;
+
+/// An error generating `info.plist`.
+#[derive(Debug)]
+pub enum Error {
+    /// More than one object has the given uid.
+    DuplicateUid(String),
+    /// A connection refers to an object or condition that doesn't exist.
+    UnknownUid(String),
+    /// Writing the property list failed.
+    Plist(plist::Error)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::DuplicateUid(ref uid) => write!(f, "more than one object has uid `{}`", uid),
+            Error::UnknownUid(ref uid) => write!(f, "connection refers to unknown uid `{}`", uid),
+            Error::Plist(ref err) => write!(f, "couldn't write info.plist: {}", err)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Plist(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<plist::Error> for Error {
+    fn from(err: plist::Error) -> Error {
+        Error::Plist(err)
+    }
+}
+
+/// A description of a workflow, written out as `info.plist`.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Workflow {
+    bundle_id: String,
+    name: String,
+    info: BTreeMap<&'static str, String>,
+    objects: Vec<Object>,
+    connections: Vec<Connection>,
+    variables: BTreeMap<String, String>,
+    dont_export: Vec<String>,
+    user_config: Vec<Field>
+}
+
+impl Workflow {
+    /// Returns a new `Workflow` with the given bundle identifier and name.
+    pub fn new<S: Into<String>, T: Into<String>>(bundle_id: S, name: T) -> Workflow {
+        Workflow {
+            bundle_id: bundle_id.into(),
+            name: name.into(),
+            info: BTreeMap::new(),
+            objects: Vec::new(),
+            connections: Vec::new(),
+            variables: BTreeMap::new(),
+            dont_export: Vec::new(),
+            user_config: Vec::new()
+        }
+    }
+
+    /// Sets the workflow version.
+    pub fn version<S: Into<String>>(self, version: S) -> Workflow {
+        self.info("version", version.into())
+    }
+
+    /// Sets the workflow author.
+    pub fn created_by<S: Into<String>>(self, author: S) -> Workflow {
+        self.info("createdby", author.into())
+    }
+
+    /// Sets the one-line description shown in Alfred's workflow list.
+    pub fn description<S: Into<String>>(self, description: S) -> Workflow {
+        self.info("description", description.into())
+    }
+
+    /// Sets the "About this Workflow" text.
+    pub fn readme<S: Into<String>>(self, readme: S) -> Workflow {
+        self.info("readme", readme.into())
+    }
+
+    /// Sets the workflow website.
+    pub fn web_address<S: Into<String>>(self, url: S) -> Workflow {
+        self.info("webaddress", url.into())
+    }
+
+    /// Sets the workflow category, e.g. `"Productivity"`.
+    pub fn category<S: Into<String>>(self, category: S) -> Workflow {
+        self.info("category", category.into())
+    }
+
+    fn info(mut self, key: &'static str, value: String) -> Workflow {
+        self.info.insert(key, value);
+        self
+    }
+
+    /// Adds an environment variable.
+    pub fn variable<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Workflow {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds an environment variable whose value isn't exported with the workflow.
+    pub fn secret_variable<K: Into<String>, V: Into<String>>(mut self, name: K, value: V)
+                                                             -> Workflow {
+        let name = name.into();
+        self.dont_export.push(name.clone());
+        self.variable(name, value)
+    }
+
+    /// Adds a user configuration field (Alfred 5 or later).
+    pub fn user_config(mut self, field: Field) -> Workflow {
+        self.user_config.push(field);
+        self
+    }
+
+    /// Adds an object.
+    pub fn object(mut self, object: Object) -> Workflow {
+        self.objects.push(object);
+        self
+    }
+
+    /// Adds a connection between two objects.
+    pub fn connection(mut self, connection: Connection) -> Workflow {
+        self.connections.push(connection);
+        self
+    }
+
+    /// Returns the `info.plist` contents.
+    ///
+    /// This fails if uids are duplicated or connections refer to objects that don't exist.
+    pub fn to_plist(&self) -> Result<Value, Error> {
+        let mut outputs = HashMap::new();
+        for object in &self.objects {
+            let conditions = match object.kind {
+                ObjectKind::Conditional(ref c) => c.conditions.iter().map(|c| &*c.uid).collect(),
+                _ => Vec::new()
+            };
+            if outputs.insert(&*object.uid, conditions).is_some() {
+                return Err(Error::DuplicateUid(object.uid.clone()));
+            }
+        }
+
+        let mut connections: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+        for connection in &self.connections {
+            let conditions = outputs.get(&*connection.from)
+                                    .ok_or_else(|| Error::UnknownUid(connection.from.clone()))?;
+            if !outputs.contains_key(&*connection.to) {
+                return Err(Error::UnknownUid(connection.to.clone()));
+            }
+            if let Some(ref output) = connection.output {
+                if !conditions.contains(&&**output) {
+                    return Err(Error::UnknownUid(output.clone()));
+                }
+            }
+            connections.entry(&connection.from).or_default().push(connection.to_plist());
+        }
+
+        let mut root = Dictionary::new();
+        root.insert("bundleid".to_owned(), string(&self.bundle_id));
+        root.insert("name".to_owned(), string(&self.name));
+        for (key, value) in &self.info {
+            root.insert((*key).to_owned(), string(value));
+        }
+        root.insert("connections".to_owned(), Value::Dictionary(
+            connections.into_iter().map(|(k, v)| (k.to_owned(), Value::Array(v))).collect()
+        ));
+        root.insert("objects".to_owned(),
+                    Value::Array(self.objects.iter().map(Object::to_plist).collect()));
+        root.insert("uidata".to_owned(), Value::Dictionary(
+            self.objects.iter().filter_map(|o| o.ui_data().map(|d| (o.uid.clone(), d))).collect()
+        ));
+        if !self.user_config.is_empty() {
+            root.insert("userconfigurationconfig".to_owned(),
+                        Value::Array(self.user_config.iter().map(Field::to_plist).collect()));
+        }
+        root.insert("variables".to_owned(), Value::Dictionary(
+            self.variables.iter().map(|(k, v)| (k.clone(), string(v))).collect()
+        ));
+        root.insert("variablesdontexport".to_owned(),
+                    Value::Array(self.dont_export.iter().map(|s| string(s)).collect()));
+        root.sort_keys();
+        Ok(Value::Dictionary(root))
+    }
+
+    /// Writes the `info.plist` contents as XML to `w`.
+    pub fn write<W: Write>(&self, w: W) -> Result<(), Error> {
+        Ok(self.to_plist()?.to_writer_xml(w)?)
+    }
+
+    /// Writes the `info.plist` contents as XML to the file at `path`.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        Ok(self.to_plist()?.to_file_xml(path)?)
+    }
+}
+
+/// An object in a workflow, along with its position on the canvas.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Object {
+    uid: String,
+    kind: ObjectKind,
+    position: Option<(f64, f64)>,
+    note: Option<String>
+}
+
+impl Object {
+    /// Returns a new `Object` with the given uid.
+    pub fn new<S: Into<String>, K: Into<ObjectKind>>(uid: S, kind: K) -> Object {
+        Object {
+            uid: uid.into(),
+            kind: kind.into(),
+            position: None,
+            note: None
+        }
+    }
+
+    /// Sets the position of the object on the canvas.
+    pub fn position(mut self, x: f64, y: f64) -> Object {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Sets the note shown below the object on the canvas.
+    pub fn note<S: Into<String>>(mut self, note: S) -> Object {
+        self.note = Some(note.into());
+        self
+    }
+
+    fn to_plist(&self) -> Value {
+        let (kind, version, config) = self.kind.to_plist();
+        Value::Dictionary(dict(vec![
+            ("config", Value::Dictionary(config)),
+            ("type", string(&kind)),
+            ("uid", string(&self.uid)),
+            ("version", Value::Integer(version.into()))
+        ]))
+    }
+
+    fn ui_data(&self) -> Option<Value> {
+        let mut data = Vec::new();
+        if let Some((x, y)) = self.position {
+            data.push(("xpos", Value::Real(x)));
+            data.push(("ypos", Value::Real(y)));
+        }
+        if let Some(ref note) = self.note {
+            data.push(("note", string(note)));
+        }
+        if data.is_empty() { None } else { Some(Value::Dictionary(dict(data))) }
+    }
+}
+
+/// The kinds of workflow objects.
+#[derive(Clone,Debug,PartialEq)]
+pub enum ObjectKind {
+    /// A keyword input.
+    Keyword(Keyword),
+    /// A script filter input.
+    ScriptFilter(ScriptFilter),
+    /// A run script action.
+    RunScript(RunScript),
+    /// An open URL action.
+    OpenUrl(OpenUrl),
+    /// A conditional utility.
+    Conditional(Conditional),
+    /// Any other object, given as its type (e.g. `"alfred.workflow.output.notification"`), the
+    /// version of its configuration, and the configuration itself.
+    Other(String, u64, Dictionary)
+}
+
+impl ObjectKind {
+    fn to_plist(&self) -> (String, u64, Dictionary) {
+        let (kind, version, config) = match *self {
+            ObjectKind::Keyword(ref k) => ("alfred.workflow.input.keyword", 1, k.to_plist()),
+            ObjectKind::ScriptFilter(ref s) => ("alfred.workflow.input.scriptfilter", 3,
+                                                s.to_plist()),
+            ObjectKind::RunScript(ref s) => ("alfred.workflow.action.script", 2, s.to_plist()),
+            ObjectKind::OpenUrl(ref o) => ("alfred.workflow.action.openurl", 1, o.to_plist()),
+            ObjectKind::Conditional(ref c) => ("alfred.workflow.utility.conditional", 1,
+                                               c.to_plist()),
+            ObjectKind::Other(ref kind, version, ref config) => {
+                return (kind.clone(), version, config.clone());
+            }
+        };
+        (kind.to_owned(), version, config)
+    }
+}
+
+macro_rules! object_kind_from {
+    ($($ty:ident),*) => {
+        $(
+            impl From<$ty> for ObjectKind {
+                fn from(value: $ty) -> ObjectKind {
+                    ObjectKind::$ty(value)
+                }
+            }
+        )*
+    }
+}
+
+object_kind_from!(Keyword, ScriptFilter, RunScript, OpenUrl, Conditional);
+
+/// Whether an input takes an argument after its keyword.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Argument {
+    /// An argument is required.
+    Required,
+    /// An argument is optional.
+    Optional,
+    /// No argument is accepted.
+    None
+}
+
+impl Argument {
+    fn to_plist(self) -> Value {
+        Value::Integer(match self {
+            Argument::Required => 0,
+            Argument::Optional => 1,
+            Argument::None => 2
+        }.into())
+    }
+}
+
+/// A keyword input.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Keyword {
+    keyword: String,
+    title: String,
+    subtext: String,
+    argument: Argument,
+    with_space: bool
+}
+
+impl Keyword {
+    /// Returns a new `Keyword` triggered by `keyword`, with no argument.
+    pub fn new<S: Into<String>>(keyword: S) -> Keyword {
+        Keyword {
+            keyword: keyword.into(),
+            title: String::new(),
+            subtext: String::new(),
+            argument: Argument::None,
+            with_space: true
+        }
+    }
+
+    /// Sets the title of the item shown for the keyword.
+    pub fn title<S: Into<String>>(mut self, title: S) -> Keyword {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the subtitle of the item shown for the keyword.
+    pub fn subtext<S: Into<String>>(mut self, subtext: S) -> Keyword {
+        self.subtext = subtext.into();
+        self
+    }
+
+    /// Sets whether the keyword takes an argument.
+    pub fn argument(mut self, argument: Argument) -> Keyword {
+        self.argument = argument;
+        self
+    }
+
+    /// Sets whether a space is required between the keyword and the argument. The default is
+    /// `true`.
+    pub fn with_space(mut self, flag: bool) -> Keyword {
+        self.with_space = flag;
+        self
+    }
+
+    fn to_plist(&self) -> Dictionary {
+        dict(vec![
+            ("argumenttype", self.argument.to_plist()),
+            ("keyword", string(&self.keyword)),
+            ("subtext", string(&self.subtext)),
+            ("text", string(&self.title)),
+            ("withspace", Value::Boolean(self.with_space))
+        ])
+    }
+}
+
+/// The language a script is run with.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Language {
+    /// `/bin/bash`
+    Bash,
+    /// `/bin/zsh`
+    Zsh,
+    /// `/usr/bin/php`
+    Php,
+    /// `/usr/bin/ruby`
+    Ruby,
+    /// `/usr/bin/python`
+    Python,
+    /// `/usr/bin/perl`
+    Perl,
+    /// `/usr/bin/osascript` (AppleScript)
+    AppleScript,
+    /// `/usr/bin/osascript` (JavaScript)
+    JavaScript,
+    /// An executable file in the workflow directory, run directly.
+    External
+}
+
+impl Language {
+    fn to_plist(self) -> Value {
+        Value::Integer(match self {
+            Language::Bash => 0,
+            Language::Php => 1,
+            Language::Ruby => 2,
+            Language::Python => 3,
+            Language::Perl => 4,
+            Language::Zsh => 5,
+            Language::AppleScript => 6,
+            Language::JavaScript => 7,
+            Language::External => 8
+        }.into())
+    }
+}
+
+/// The script run by a script filter or run script action.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Script {
+    language: Language,
+    source: String,
+    argv: bool,
+    escaping: Escaping
+}
+
+impl Script {
+    /// Returns a new `Script` with the given language and source.
+    ///
+    /// The input is passed as the first argument (`$1` or `argv`), which doesn't need escaping.
+    pub fn new<S: Into<String>>(language: Language, source: S) -> Script {
+        Script {
+            language,
+            source: source.into(),
+            argv: true,
+            escaping: Escaping::default()
+        }
+    }
+
+    /// Returns a new bash `Script` with the given source.
+    pub fn bash<S: Into<String>>(source: S) -> Script {
+        Script::new(Language::Bash, source)
+    }
+
+    /// Returns a new `Script` that runs an executable file, given relative to the workflow
+    /// directory.
+    ///
+    /// This is the usual way to run a workflow binary.
+    pub fn file<S: Into<String>>(path: S) -> Script {
+        Script::new(Language::External, path)
+    }
+
+    /// Substitutes the input for `{query}` in the script instead of passing it as an argument,
+    /// escaping the given characters.
+    ///
+    /// This has no effect on `Language::External` scripts.
+    pub fn query(mut self, escaping: Escaping) -> Script {
+        self.argv = false;
+        self.escaping = escaping;
+        self
+    }
+
+    fn insert_into(&self, config: &mut Vec<(&'static str, Value)>) {
+        let (script, file) = match self.language {
+            Language::External => ("", &*self.source),
+            _ => (&*self.source, "")
+        };
+        config.push(("escaping", Value::Integer(self.escaping.bits().into())));
+        config.push(("script", string(script)));
+        config.push(("scriptargtype", Value::Integer(if self.argv { 1 } else { 0 }.into())));
+        config.push(("scriptfile", string(file)));
+        config.push(("type", self.language.to_plist()));
+    }
+}
+
+/// What a script filter does when the query changes while its script is still running.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum QueueMode {
+    /// Wait for the running script to finish before running it again.
+    Wait,
+    /// Terminate the running script and run it again immediately.
+    Terminate
+}
+
+/// How Alfred matches the query against the titles of a script filter's items when it filters
+/// the results itself.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum MatchMode {
+    /// Match words in the query against the start of words in the title, in order.
+    WordBoundary,
+    /// Match the query anywhere in the title.
+    Anywhere,
+    /// Match words in the query against the start of words in the title, in any order.
+    WordBoundaryAnyOrder
+}
+
+/// A script filter input.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ScriptFilter {
+    keyword: String,
+    script: Script,
+    title: String,
+    subtext: String,
+    running_subtext: String,
+    argument: Argument,
+    with_space: bool,
+    queue_mode: QueueMode,
+    alfred_filters: Option<MatchMode>
+}
+
+impl ScriptFilter {
+    /// Returns a new `ScriptFilter` triggered by `keyword` that runs `script`, with an optional
+    /// argument.
+    pub fn new<S: Into<String>>(keyword: S, script: Script) -> ScriptFilter {
+        ScriptFilter {
+            keyword: keyword.into(),
+            script,
+            title: String::new(),
+            subtext: String::new(),
+            running_subtext: String::new(),
+            argument: Argument::Optional,
+            with_space: true,
+            queue_mode: QueueMode::Terminate,
+            alfred_filters: None
+        }
+    }
+
+    /// Sets the title of the item shown for the keyword before the script runs.
+    pub fn title<S: Into<String>>(mut self, title: S) -> ScriptFilter {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the subtitle of the item shown for the keyword before the script runs.
+    pub fn subtext<S: Into<String>>(mut self, subtext: S) -> ScriptFilter {
+        self.subtext = subtext.into();
+        self
+    }
+
+    /// Sets the subtitle shown while the script is running.
+    pub fn running_subtext<S: Into<String>>(mut self, subtext: S) -> ScriptFilter {
+        self.running_subtext = subtext.into();
+        self
+    }
+
+    /// Sets whether the keyword takes an argument. The default is `Argument::Optional`.
+    pub fn argument(mut self, argument: Argument) -> ScriptFilter {
+        self.argument = argument;
+        self
+    }
+
+    /// Sets whether a space is required between the keyword and the argument. The default is
+    /// `true`.
+    pub fn with_space(mut self, flag: bool) -> ScriptFilter {
+        self.with_space = flag;
+        self
+    }
+
+    /// Sets what happens when the query changes while the script is running. The default is
+    /// `QueueMode::Terminate`.
+    pub fn queue_mode(mut self, mode: QueueMode) -> ScriptFilter {
+        self.queue_mode = mode;
+        self
+    }
+
+    /// Has Alfred filter the script's results against the query, so the script only runs once.
+    pub fn alfred_filters_results(mut self, mode: MatchMode) -> ScriptFilter {
+        self.alfred_filters = Some(mode);
+        self
+    }
+
+    fn to_plist(&self) -> Dictionary {
+        let match_mode = match self.alfred_filters {
+            None | Some(MatchMode::WordBoundary) => 0,
+            Some(MatchMode::Anywhere) => 1,
+            Some(MatchMode::WordBoundaryAnyOrder) => 2
+        };
+        let mut config = vec![
+            ("alfredfiltersresults", Value::Boolean(self.alfred_filters.is_some())),
+            ("alfredfiltersresultsmatchmode", Value::Integer(match_mode.into())),
+            ("argumenttreatemptyqueryasnil", Value::Boolean(true)),
+            ("argumenttrimmode", Value::Integer(0.into())),
+            ("argumenttype", self.argument.to_plist()),
+            ("keyword", string(&self.keyword)),
+            ("queuedelaycustom", Value::Integer(3.into())),
+            ("queuedelayimmediatelyinitially", Value::Boolean(true)),
+            ("queuedelaymode", Value::Integer(0.into())),
+            ("queuemode", Value::Integer(match self.queue_mode {
+                QueueMode::Wait => 1,
+                QueueMode::Terminate => 2
+            }.into())),
+            ("runningsubtext", string(&self.running_subtext)),
+            ("subtext", string(&self.subtext)),
+            ("title", string(&self.title)),
+            ("withspace", Value::Boolean(self.with_space))
+        ];
+        self.script.insert_into(&mut config);
+        dict(config)
+    }
+}
+
+/// A run script action.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct RunScript {
+    script: Script,
+    concurrently: bool
+}
+
+impl RunScript {
+    /// Returns a new `RunScript` that runs `script`.
+    pub fn new(script: Script) -> RunScript {
+        RunScript {
+            script,
+            concurrently: false
+        }
+    }
+
+    /// Sets whether instances of the script can run at the same time. By default they run one
+    /// after another.
+    pub fn concurrently(mut self, flag: bool) -> RunScript {
+        self.concurrently = flag;
+        self
+    }
+
+    fn to_plist(&self) -> Dictionary {
+        let mut config = vec![("concurrently", Value::Boolean(self.concurrently))];
+        self.script.insert_into(&mut config);
+        dict(config)
+    }
+}
+
+/// An open URL action.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct OpenUrl {
+    url: String,
+    browser: String,
+    utf8: bool
+}
+
+impl OpenUrl {
+    /// Returns a new `OpenUrl` that opens `url` in the default browser.
+    ///
+    /// `{query}` in the URL is replaced with the input.
+    pub fn new<S: Into<String>>(url: S) -> OpenUrl {
+        OpenUrl {
+            url: url.into(),
+            browser: String::new(),
+            utf8: true
+        }
+    }
+
+    /// Sets the bundle identifier of the browser to open the URL in.
+    pub fn browser<S: Into<String>>(mut self, bundle_id: S) -> OpenUrl {
+        self.browser = bundle_id.into();
+        self
+    }
+
+    /// Sets whether the input is encoded as UTF-8 before being substituted into the URL. The
+    /// default is `true`.
+    pub fn utf8(mut self, flag: bool) -> OpenUrl {
+        self.utf8 = flag;
+        self
+    }
+
+    fn to_plist(&self) -> Dictionary {
+        dict(vec![
+            ("browser", string(&self.browser)),
+            ("spaces", string("")),
+            ("url", string(&self.url)),
+            ("utf8", Value::Boolean(self.utf8))
+        ])
+    }
+}
+
+/// How a condition compares its input with its value.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Comparison {
+    /// The input is equal to the value.
+    Equal,
+    /// The input isn't equal to the value.
+    NotEqual,
+    /// The input is greater than the value.
+    GreaterThan,
+    /// The input is less than the value.
+    LessThan,
+    /// The input matches the value as a regular expression.
+    Regex
+}
+
+/// A condition in a `Conditional`.
+///
+/// Each condition is an output of the conditional, identified by its uid. Connect it with
+/// `Connection::from_output()`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Condition {
+    uid: String,
+    input: String,
+    comparison: Comparison,
+    value: String,
+    case_sensitive: bool,
+    label: String
+}
+
+impl Condition {
+    /// Returns a new `Condition` comparing `input` with `value`.
+    ///
+    /// `input` is usually `{query}` or a variable such as `{var:mode}`.
+    pub fn new<S, I, V>(uid: S, input: I, comparison: Comparison, value: V) -> Condition
+        where S: Into<String>, I: Into<String>, V: Into<String>
+    {
+        Condition {
+            uid: uid.into(),
+            input: input.into(),
+            comparison,
+            value: value.into(),
+            case_sensitive: false,
+            label: String::new()
+        }
+    }
+
+    /// Sets whether the comparison is case sensitive. The default is `false`.
+    pub fn case_sensitive(mut self, flag: bool) -> Condition {
+        self.case_sensitive = flag;
+        self
+    }
+
+    /// Sets the label of the condition's output on the canvas.
+    pub fn label<S: Into<String>>(mut self, label: S) -> Condition {
+        self.label = label.into();
+        self
+    }
+
+    fn to_plist(&self) -> Value {
+        Value::Dictionary(dict(vec![
+            ("inputstring", string(&self.input)),
+            ("matchcasesensitive", Value::Boolean(self.case_sensitive)),
+            ("matchmode", Value::Integer(match self.comparison {
+                Comparison::Equal => 0,
+                Comparison::NotEqual => 1,
+                Comparison::GreaterThan => 2,
+                Comparison::LessThan => 3,
+                Comparison::Regex => 4
+            }.into())),
+            ("matchstring", string(&self.value)),
+            ("outputlabel", string(&self.label)),
+            ("uid", string(&self.uid))
+        ]))
+    }
+}
+
+/// A conditional utility, which passes its input to the output of the first condition that
+/// matches, or to its "else" output.
+///
+/// Connections made without `Connection::from_output()` come from the "else" output.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Conditional {
+    conditions: Vec<Condition>,
+    else_label: String,
+    hide_else: bool
+}
+
+impl Conditional {
+    /// Returns a new `Conditional` with no conditions.
+    pub fn new() -> Conditional {
+        Conditional::default()
+    }
+
+    /// Adds a condition.
+    pub fn condition(mut self, condition: Condition) -> Conditional {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Sets the label of the "else" output.
+    pub fn else_label<S: Into<String>>(mut self, label: S) -> Conditional {
+        self.else_label = label.into();
+        self
+    }
+
+    /// Sets whether the "else" output is hidden.
+    pub fn hide_else(mut self, flag: bool) -> Conditional {
+        self.hide_else = flag;
+        self
+    }
+
+    fn to_plist(&self) -> Dictionary {
+        dict(vec![
+            ("conditions", Value::Array(self.conditions.iter().map(Condition::to_plist).collect())),
+            ("elselabel", string(&self.else_label)),
+            ("hideelse", Value::Boolean(self.hide_else))
+        ])
+    }
+}
+
+/// A connection from one object to another.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Connection {
+    from: String,
+    to: String,
+    output: Option<String>,
+    modifiers: Vec<Modifier>,
+    modifier_subtext: String
+}
+
+impl Connection {
+    /// Returns a new `Connection` from the object with uid `from` to the object with uid `to`.
+    pub fn new<S: Into<String>, T: Into<String>>(from: S, to: T) -> Connection {
+        Connection {
+            from: from.into(),
+            to: to.into(),
+            output: None,
+            modifiers: Vec::new(),
+            modifier_subtext: String::new()
+        }
+    }
+
+    /// Connects from the output of a condition, given by its uid, rather than the object's
+    /// default output.
+    pub fn from_output<S: Into<String>>(mut self, uid: S) -> Connection {
+        self.output = Some(uid.into());
+        self
+    }
+
+    /// Sets the modifier keys that must be held for the connection to be followed.
+    pub fn modifiers(mut self, modifiers: &[Modifier]) -> Connection {
+        self.modifiers = modifiers.to_vec();
+        self
+    }
+
+    /// Sets the subtitle shown while the modifier keys are held.
+    pub fn modifier_subtext<S: Into<String>>(mut self, subtext: S) -> Connection {
+        self.modifier_subtext = subtext.into();
+        self
+    }
+
+    fn to_plist(&self) -> Value {
+        let mut modifiers = 0u64;
+        for modifier in &self.modifiers {
+            // NSEvent modifier flags.
+            modifiers |= match *modifier {
+                Modifier::Shift => 1 << 17,
+                Modifier::Control => 1 << 18,
+                Modifier::Option => 1 << 19,
+                Modifier::Command => 1 << 20,
+                Modifier::Fn => 1 << 23
+            };
+        }
+        let mut connection = vec![
+            ("destinationuid", string(&self.to)),
+            ("modifiers", Value::Integer(modifiers.into())),
+            ("modifiersubtext", string(&self.modifier_subtext)),
+            ("vitoclose", Value::Boolean(false))
+        ];
+        if let Some(ref output) = self.output {
+            connection.push(("sourceoutputuid", string(output)));
+        }
+        Value::Dictionary(dict(connection))
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_owned())
+}
+
+fn dict(entries: Vec<(&str, Value)>) -> Dictionary {
+    entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect()
+}
+
+#[test]
+fn test_workflow() {
+    let workflow = Workflow::new("com.example.test", "Test")
+        .version("1.0")
+        .secret_variable("TOKEN", "abc")
+        .object(Object::new("A", ScriptFilter::new("t", Script::file("./main"))
+                                              .alfred_filters_results(MatchMode::Anywhere))
+                       .position(10.0, 20.0)
+                       .note("entry point"))
+        .object(Object::new("B", Conditional::new()
+                                    .condition(Condition::new("B1", "{query}", Comparison::Regex,
+                                                              "^https?:"))))
+        .object(Object::new("C", OpenUrl::new("{query}")))
+        .object(Object::new("D", RunScript::new(Script::bash("echo {query}")
+                                                    .query(Escaping::default()
+                                                           | Escaping::SPACES))))
+        .connection(Connection::new("A", "B"))
+        .connection(Connection::new("B", "C").from_output("B1"))
+        .connection(Connection::new("B", "D").modifiers(&[Modifier::Command, Modifier::Shift]));
+
+    let plist = workflow.to_plist().unwrap();
+    let root = plist.as_dictionary().unwrap();
+    assert_eq!(root.keys().map(|k| &**k).collect::<Vec<_>>(),
+               ["bundleid", "connections", "name", "objects", "uidata", "variables",
+                "variablesdontexport", "version"]);
+    let objects = root["objects"].as_array().unwrap();
+    let filter = objects[0].as_dictionary().unwrap();
+    assert_eq!(filter["type"].as_string(), Some("alfred.workflow.input.scriptfilter"));
+    let config = filter["config"].as_dictionary().unwrap();
+    assert_eq!(config["scriptfile"].as_string(), Some("./main"));
+    assert_eq!(config["type"].as_unsigned_integer(), Some(8));
+    assert_eq!(config["alfredfiltersresults"].as_boolean(), Some(true));
+    assert_eq!(config["alfredfiltersresultsmatchmode"].as_unsigned_integer(), Some(1));
+    let config = objects[3].as_dictionary().unwrap()["config"].as_dictionary().unwrap();
+    assert_eq!(config["escaping"].as_unsigned_integer(), Some(103));
+    assert_eq!(config["scriptargtype"].as_unsigned_integer(), Some(0));
+
+    let connections = root["connections"].as_dictionary().unwrap();
+    let from_b = connections["B"].as_array().unwrap();
+    assert_eq!(from_b[0].as_dictionary().unwrap()["sourceoutputuid"].as_string(), Some("B1"));
+    assert_eq!(from_b[1].as_dictionary().unwrap()["modifiers"].as_unsigned_integer(),
+               Some(1_179_648));
+    let uidata = root["uidata"].as_dictionary().unwrap();
+    assert_eq!(uidata["A"].as_dictionary().unwrap()["xpos"].as_real(), Some(10.0));
+    assert!(uidata.get("B").is_none());
+    assert_eq!(root["variablesdontexport"].as_array().unwrap()[0].as_string(), Some("TOKEN"));
+
+    let mut xml = Vec::new();
+    workflow.write(&mut xml).unwrap();
+    assert_eq!(Value::from_reader_xml(&xml[..]).unwrap(), plist);
+
+    match workflow.clone().connection(Connection::new("A", "E")).to_plist() {
+        Err(Error::UnknownUid(ref uid)) if uid == "E" => {}
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match workflow.clone().connection(Connection::new("A", "C").from_output("B1")).to_plist() {
+        Err(Error::UnknownUid(ref uid)) if uid == "B1" => {}
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match workflow.object(Object::new("A", OpenUrl::new(""))).to_plist() {
+        Err(Error::DuplicateUid(ref uid)) if uid == "A" => {}
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
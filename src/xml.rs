@@ -3,6 +3,9 @@
 //! Unless you specifically need Alfred 2 compatibility, you should use the `alfred::json` module
 //! instead.
 //!
+//! With the `tokio` feature, `AsyncXMLWriter` and `write_items_async()` write to a tokio
+//! `AsyncWrite` instead.
+//!
 //! # Example
 //!
 //! ```
@@ -11,7 +14,7 @@
 //! # use std::io::{self, Write};
 //! #
 //! # fn write_items() -> io::Result<()> {
-//! let mut xmlw = try!(alfred::XMLWriter::new(io::stdout()));
+//! let mut xmlw = alfred::XMLWriter::new(io::stdout())?;
 //!
 //! let item1 = alfred::Item::new("Item 1");
 //! let item2 = alfred::ItemBuilder::new("Item 2")
@@ -23,11 +26,11 @@
 //!                                 .icon_filetype("public.folder")
 //!                                 .into_item();
 //!
-//! try!(xmlw.write_item(&item1));
-//! try!(xmlw.write_item(&item2));
-//! try!(xmlw.write_item(&item3));
+//! xmlw.write_item(&item1)?;
+//! xmlw.write_item(&item2)?;
+//! xmlw.write_item(&item3)?;
 //!
-//! let mut stdout = try!(xmlw.close());
+//! let mut stdout = xmlw.close()?;
 //! stdout.flush()
 //! # }
 //! #
@@ -48,8 +51,18 @@ use std::io;
 use std::io::prelude::*;
 use std::mem;
 use std::sync;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
 
 use ::{Item, ItemType, Modifier, Icon};
+#[cfg(feature = "tokio")]
+use asyncio::{poll_write_buf, WriteAndFlush};
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncWrite;
 
 /// Helper struct used to manage the XML serialization of `Item`s.
 ///
@@ -100,11 +113,7 @@ impl SavedError {
 }
 
 impl error::Error for SharedError {
-    fn description(&self) -> &str {
-        self.error.description()
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         Some(&*self.error)
     }
 }
@@ -126,7 +135,7 @@ impl<W: Write> XMLWriter<W> {
     ///
     /// The XML header is written immediately.
     pub fn new(mut w: W) -> io::Result<XMLWriter<W>> {
-        match w.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<items>\n") {
+        match w.write_all(HEADER) {
             Ok(()) => {
                 Ok(XMLWriter {
                     w: Some(w),
@@ -179,13 +188,16 @@ impl<W: Write> XMLWriter<W> {
         if let Some(err) = last_err {
             return Err(err.make_io_error());
         }
-        try!(write_footer(&mut w));
+        write_footer(&mut w)?;
         Ok(w)
     }
 }
 
+const HEADER: &[u8] = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<items>\n";
+const FOOTER: &[u8] = b"</items>\n";
+
 fn write_footer<W: Write>(w: &mut W) -> io::Result<()> {
-    w.write_all(b"</items>\n")
+    w.write_all(FOOTER)
 }
 
 impl<W: Write> Drop for XMLWriter<W> {
@@ -204,132 +216,262 @@ impl<W: Write> Drop for XMLWriter<W> {
 ///
 /// The `Write` is flushed after the XML document is written.
 pub fn write_items<W: Write>(w: W, items: &[Item]) -> io::Result<()> {
-    let mut xmlw = try!(XMLWriter::new(w));
+    let mut xmlw = XMLWriter::new(w)?;
     for item in items.iter() {
-        try!(xmlw.write_item(item));
+        xmlw.write_item(item)?;
     }
-    let mut w = try!(xmlw.close());
+    let mut w = xmlw.close()?;
     w.flush()
 }
 
+/// Helper struct used to manage the XML serialization of `Item`s to an `AsyncWrite`.
+///
+/// This is the async version of `XMLWriter`. A footer can't be written asynchronously when the
+/// writer is dropped, so `close()` must be called to finish the XML document.
+///
+/// Each method serializes into an internal buffer and returns a future that writes the buffer
+/// out. If such a future is dropped before it completes, the rest of the buffer is written by the
+/// next call, so the document is never left with a partial item in the middle.
+#[cfg(feature = "tokio")]
+pub struct AsyncXMLWriter<W: AsyncWrite + Unpin> {
+    w: W,
+    buf: Vec<u8>,
+    pos: usize,
+    last_err: Option<SavedError>
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncXMLWriter<W> {
+    /// Returns a new `AsyncXMLWriter` that writes to the given `AsyncWrite`.
+    ///
+    /// The XML header is written along with the first item, or by `close()` if there are no
+    /// items.
+    pub fn new(w: W) -> AsyncXMLWriter<W> {
+        AsyncXMLWriter {
+            w,
+            buf: HEADER.to_vec(),
+            pos: 0,
+            last_err: None
+        }
+    }
+
+    /// Writes an `Item` to the underlying `AsyncWrite`.
+    ///
+    /// The item is serialized before this method returns, so the future doesn't borrow it.
+    ///
+    /// If a previous write produced an error, any subsequent write will do nothing and return the
+    /// same error, as with `XMLWriter::write_item()`.
+    pub fn write_item(&mut self, item: &Item) -> impl Future<Output=io::Result<()>> + '_ {
+        if self.last_err.is_none() {
+            // Writing to a Vec can't fail.
+            let _ = item.write_xml(&mut self.buf, 1);
+        }
+        Drain { writer: self }
+    }
+
+    /// Consumes the `AsyncXMLWriter` and writes the XML footer.
+    ///
+    /// As with `write_item()`, if a previous write returned an error, the future resolves to the
+    /// same error without attempting to write the XML footer.
+    ///
+    /// As with `XMLWriter::close()`, the XML footer is written, but the `AsyncWrite` is not
+    /// flushed.
+    pub fn close(mut self) -> impl Future<Output=io::Result<W>> {
+        if self.last_err.is_none() {
+            self.buf.extend_from_slice(FOOTER);
+        }
+        Close { writer: Some(self) }
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        if let Some(ref err) = self.last_err {
+            return Poll::Ready(Err(err.make_io_error()));
+        }
+        match poll_write_buf(&mut self.w, cx, &self.buf, &mut self.pos) {
+            Poll::Ready(Ok(())) => {
+                self.buf.clear();
+                self.pos = 0;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(err)) => {
+                let err: SavedError = err.into();
+                let io_err = err.make_io_error();
+                self.last_err = Some(err);
+                Poll::Ready(Err(io_err))
+            }
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+struct Drain<'a, W: AsyncWrite + Unpin + 'a> {
+    writer: &'a mut AsyncXMLWriter<W>
+}
+
+#[cfg(feature = "tokio")]
+impl<'a, W: AsyncWrite + Unpin> Future for Drain<'a, W> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().writer.poll_drain(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+struct Close<W: AsyncWrite + Unpin> {
+    // Option so the writer can be returned once the footer is written
+    writer: Option<AsyncXMLWriter<W>>
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> Future for Close<W> {
+    type Output = io::Result<W>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<W>> {
+        let this = self.get_mut();
+        match this.writer.as_mut().expect("polled after completion").poll_drain(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(this.writer.take().unwrap().w)),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+/// Writes a complete XML document representing the `Item`s to the `AsyncWrite`.
+///
+/// The `AsyncWrite` is flushed after the XML document is written.
+///
+/// This is the async version of `write_items()`. The items are serialized before this function
+/// returns, so the future doesn't borrow them.
+#[cfg(feature = "tokio")]
+pub fn write_items_async<W>(w: W, items: &[Item]) -> impl Future<Output=io::Result<()>>
+    where W: AsyncWrite + Unpin
+{
+    let mut buf = Vec::new();
+    // Writing to a Vec can't fail.
+    let _ = write_items(&mut buf, items);
+    WriteAndFlush::new(w, buf)
+}
+
 impl<'a> Item<'a> {
     /// Writes the XML fragment representing the `Item` to the `Write`.
     ///
     /// `XMLWriter` should be used instead if at all possible, in order to
     /// write the XML header/footer and maintain proper error discipline.
-    pub fn write_xml(&self, w: &mut Write, indent: u32) -> io::Result<()> {
-        fn write_indent(w: &mut Write, indent: u32) -> io::Result<()> {
+    pub fn write_xml(&self, w: &mut dyn Write, indent: u32) -> io::Result<()> {
+        fn write_indent(w: &mut dyn Write, indent: u32) -> io::Result<()> {
             for _ in 0..indent {
-                try!(w.write_all(b"    "));
+                w.write_all(b"    ")?;
             }
             Ok(())
         }
 
         let mut w = io::BufWriter::with_capacity(512, w);
 
-        try!(write_indent(&mut w, indent));
-        try!(w.write_all(b"<item"));
+        write_indent(&mut w, indent)?;
+        w.write_all(b"<item")?;
         if let Some(ref uid) = self.uid {
-            try!(write!(&mut w, r#" uid="{}""#, encode_entities(uid)));
+            write!(&mut w, r#" uid="{}""#, encode_entities(uid))?;
         }
         if let Some(ref arg) = self.arg {
-            try!(write!(&mut w, r#" arg="{}""#, encode_entities(arg)));
+            write!(&mut w, r#" arg="{}""#, encode_entities(arg))?;
         }
         match self.type_ {
             ItemType::Default => {}
             ItemType::File => {
-                try!(w.write_all(br#" type="file""#));
+                w.write_all(br#" type="file""#)?;
             }
             ItemType::FileSkipCheck => {
-                try!(w.write_all(br#" type="file:skipcheck""#));
+                w.write_all(br#" type="file:skipcheck""#)?;
             }
         }
         if !self.valid {
-            try!(w.write_all(br#" valid="no""#));
+            w.write_all(br#" valid="no""#)?;
         }
         if let Some(ref auto) = self.autocomplete {
-            try!(write!(&mut w, r#" autocomplete="{}""#, encode_entities(auto)));
+            write!(&mut w, r#" autocomplete="{}""#, encode_entities(auto))?;
         }
-        try!(w.write_all(b">\n"));
+        w.write_all(b">\n")?;
 
-        try!(write_indent(&mut w, indent+1));
-        try!(write!(&mut w, "<title>{}</title>\n", encode_entities(&self.title)));
+        write_indent(&mut w, indent+1)?;
+        writeln!(&mut w, "<title>{}</title>", encode_entities(&self.title))?;
 
         if let Some(ref subtitle) = self.subtitle {
-            try!(write_indent(&mut w, indent+1));
-            try!(write!(&mut w, "<subtitle>{}</subtitle>\n", encode_entities(subtitle)));
+            write_indent(&mut w, indent+1)?;
+            writeln!(&mut w, "<subtitle>{}</subtitle>", encode_entities(subtitle))?;
         }
 
         if let Some(ref icon) = self.icon {
-            try!(write_indent(&mut w, indent+1));
+            write_indent(&mut w, indent+1)?;
             match *icon {
                 Icon::Path(ref s) => {
-                    try!(write!(&mut w, "<icon>{}</icon>\n", encode_entities(s)));
+                    writeln!(&mut w, "<icon>{}</icon>", encode_entities(s))?;
                 }
                 Icon::File(ref s) => {
-                    try!(write!(&mut w, "<icon type=\"fileicon\">{}</icon>\n",
-                                    encode_entities(s)));
+                    writeln!(&mut w, "<icon type=\"fileicon\">{}</icon>",
+                                    encode_entities(s))?;
                 }
                 Icon::FileType(ref s) => {
-                    try!(write!(&mut w, "<icon type=\"filetype\">{}</icon>\n",
-                                    encode_entities(s)));
+                    writeln!(&mut w, "<icon type=\"filetype\">{}</icon>",
+                                    encode_entities(s))?;
                 }
             }
         }
 
         for (modifier, data) in &self.modifiers {
-            try!(write_indent(&mut w, indent+1));
-            try!(write!(&mut w, r#"<mod key="{}""#, match *modifier {
+            write_indent(&mut w, indent+1)?;
+            write!(&mut w, r#"<mod key="{}""#, match *modifier {
                 Modifier::Command => "cmd",
                 Modifier::Option => "alt",
                 Modifier::Control => "ctrl",
                 Modifier::Shift => "shift",
                 Modifier::Fn => "fn"
-            }));
-            try!(w.write_all(b"<mod"));
+            })?;
+            w.write_all(b"<mod")?;
             if let Some(ref subtitle) = data.subtitle {
-                try!(write!(&mut w, r#" subtitle="{}""#, encode_entities(subtitle)));
+                write!(&mut w, r#" subtitle="{}""#, encode_entities(subtitle))?;
             }
             if let Some(ref arg) = data.arg {
-                try!(write!(&mut w, r#" arg="{}""#, encode_entities(arg)));
+                write!(&mut w, r#" arg="{}""#, encode_entities(arg))?;
             }
             if let Some(valid) = data.valid {
-                try!(write!(&mut w, r#" valid="{}""#, if valid { "yes" } else { "no" }));
+                write!(&mut w, r#" valid="{}""#, if valid { "yes" } else { "no" })?;
             }
-            try!(w.write_all(b"/>\n"));
+            w.write_all(b"/>\n")?;
         }
 
         if let Some(ref text) = self.text_copy {
-            try!(write_indent(&mut w, indent+1));
-            try!(write!(&mut w, "<text type=\"copy\">{}</text>\n", encode_entities(text)));
+            write_indent(&mut w, indent+1)?;
+            writeln!(&mut w, "<text type=\"copy\">{}</text>", encode_entities(text))?;
         }
         if let Some(ref text) = self.text_large_type {
-            try!(write_indent(&mut w, indent+1));
-            try!(write!(&mut w, "<text type=\"largetype\">{}</text>\n", encode_entities(text)));
+            write_indent(&mut w, indent+1)?;
+            writeln!(&mut w, "<text type=\"largetype\">{}</text>", encode_entities(text))?;
         }
 
         if let Some(ref url) = self.quicklook_url {
-            try!(write_indent(&mut w, indent+1));
-            try!(write!(&mut w, "<quicklookurl>{}</quicklookurl>\n", encode_entities(url)));
+            write_indent(&mut w, indent+1)?;
+            writeln!(&mut w, "<quicklookurl>{}</quicklookurl>", encode_entities(url))?;
         }
 
-        try!(write_indent(&mut w, indent));
-        try!(w.write_all(b"</item>\n"));
+        write_indent(&mut w, indent)?;
+        w.write_all(b"</item>\n")?;
 
         w.flush()
     }
 }
 
-fn encode_entities(s: &str) -> Cow<str> {
+fn encode_entities(s: &str) -> Cow<'_, str> {
     fn encode_entity(c: char) -> Option<&'static str> {
         Some(match c {
             '<' => "&lt;",
             '>' => "&gt;",
             '"' => "&quot;",
             '&' => "&amp;",
-            '\0'...'\x08' |
-            '\x0B'...'\x0C' |
-            '\x0E'...'\x1F' |
+            '\0'..='\x08' |
+            '\x0B'..='\x0C' |
+            '\x0E'..='\x1F' |
             '\u{FFFE}' | '\u{FFFF}' => {
                 // these are all invalid characters in XML
                 "\u{FFFD}"
@@ -351,3 +493,26 @@ fn encode_entities(s: &str) -> Cow<str> {
         Cow::Borrowed(s)
     }
 }
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_write_async() {
+    use asyncio::{block_on, Trickle};
+
+    let items = [Item::new("Item 1"), ::ItemBuilder::new("<Item & 2>").arg("arg").into_item()];
+    let mut expected = Vec::new();
+    write_items(&mut expected, &items).unwrap();
+
+    let mut output = Trickle::default();
+    block_on(write_items_async(&mut output, &items)).unwrap();
+    assert_eq!(output.data, expected);
+    assert!(output.flushed);
+
+    let mut xmlw = AsyncXMLWriter::new(Trickle::default());
+    for item in &items {
+        block_on(xmlw.write_item(item)).unwrap();
+    }
+    let output = block_on(xmlw.close()).unwrap();
+    assert_eq!(output.data, expected);
+    assert!(!output.flushed);
+}